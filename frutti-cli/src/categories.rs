@@ -0,0 +1,120 @@
+use crate::config::{cache_path, Config, Theme};
+use anyhow::{Context, Result};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+use std::time::Duration;
+use tutti_frutti::categories::{cached_categories, CategoryTree};
+
+/// Categories rarely change, refresh the cached tree once a week.
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Loads the category tree from the cache, fetching it if outdated.
+pub async fn load(config: &Config) -> Result<CategoryTree> {
+    let path = cache_path("categories.json").context("No cache directory available")?;
+    cached_categories(&path, MAX_AGE, &config.network)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to load categories: {}", e))
+}
+
+/// `frutti-cli categories`: prints the category tree with the ID of each category.
+pub async fn print_tree(config: &Config) -> Result<()> {
+    let tree = load(config).await?;
+    for (depth, category) in tree.flatten() {
+        println!("{}{} ({})", "  ".repeat(depth), category.name, category.id);
+    }
+    Ok(())
+}
+
+/// The category searches are limited to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedCategory {
+    pub id: String,
+    /// E.g. "Fahrzeuge › Velos".
+    pub label: String,
+}
+
+/// Popup listing every category, indented by depth.
+pub struct CategoryPicker {
+    // Depth, name and the category, `None` for "All categories" shown first
+    entries: Vec<(usize, String, Option<SelectedCategory>)>,
+    state: ListState,
+}
+
+impl CategoryPicker {
+    /// Opens the picker on the `current` category.
+    pub fn new(tree: &CategoryTree, current: Option<&SelectedCategory>) -> Self {
+        let mut entries = vec![(0, "All categories".to_string(), None)];
+        for (depth, category) in tree.flatten() {
+            let label = tree
+                .path(&category.id)
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>()
+                .join(" › ");
+            entries.push((
+                depth,
+                category.name.clone(),
+                Some(SelectedCategory {
+                    id: category.id.clone(),
+                    label,
+                }),
+            ));
+        }
+        let selected = entries
+            .iter()
+            .position(|(_, _, entry)| entry.as_ref() == current)
+            .unwrap_or(0);
+        let mut state = ListState::default();
+        state.select(Some(selected));
+        CategoryPicker { entries, state }
+    }
+
+    pub fn next(&mut self) {
+        let i = self.state.selected().unwrap_or(0);
+        self.state.select(Some((i + 1) % self.entries.len()));
+    }
+
+    pub fn previous(&mut self) {
+        let i = self.state.selected().unwrap_or(0);
+        self.state
+            .select(Some((i + self.entries.len() - 1) % self.entries.len()));
+    }
+
+    /// The highlighted category, `None` for all categories.
+    pub fn selected(&self) -> Option<SelectedCategory> {
+        self.entries[self.state.selected().unwrap_or(0)].2.clone()
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let width = (area.width * 3 / 5).max(30).min(area.width);
+        let height = (area.height * 4 / 5).max(5).min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        let items: Vec<ListItem> = self
+            .entries
+            .iter()
+            .map(|(depth, name, _)| {
+                ListItem::new(Line::from(format!("{}{}", "  ".repeat(*depth), name)))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Category"))
+            .style(Style::default().fg(theme.title))
+            .highlight_style(
+                Style::default()
+                    .bg(theme.highlight)
+                    .add_modifier(Modifier::BOLD),
+            );
+        f.render_widget(Clear, popup);
+        f.render_stateful_widget(list, popup, &mut self.state);
+    }
+}
@@ -0,0 +1,764 @@
+use crate::sort::{SortCategory, SortKey};
+use crate::thumbnail::ImageProtocol;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::Color;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tutti_frutti::cache::ResponseCache;
+use tutti_frutti::geo::{self, Coordinates};
+use tutti_frutti::graphql::read_search_document;
+use tutti_frutti::locale::Locale;
+use tutti_frutti::network::ClientOptions;
+use tutti_frutti::synonyms::Synonyms;
+use tutti_frutti::SearchConfig;
+
+/// Path of a file in `$XDG_DATA_HOME/frutti-cli`, where state between runs is kept.
+pub fn data_path(file: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("frutti-cli").join(file))
+}
+
+/// Path in `$XDG_CACHE_HOME/frutti-cli`, for anything that can be downloaded again.
+pub fn cache_path(name: &str) -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("frutti-cli").join(name))
+}
+
+/// Actions that can be bound to keys in the listing and stats views.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Next,
+    Previous,
+    Search,
+    Sort,
+    SortDirection,
+    SecondarySort,
+    SecondarySortDirection,
+    ToggleNew,
+    Stats,
+    ToggleOutliers,
+    CycleBinning,
+    Open,
+    Back,
+    ToggleView,
+    NextColumn,
+    PreviousColumn,
+    WidenColumn,
+    NarrowColumn,
+    ToggleReposts,
+    TogglePreview,
+    ToggleDistance,
+    ShowSeller,
+    PickCategory,
+}
+
+impl Action {
+    const ALL: [Action; 24] = [
+        Action::Quit,
+        Action::Next,
+        Action::Previous,
+        Action::Search,
+        Action::Sort,
+        Action::SortDirection,
+        Action::SecondarySort,
+        Action::SecondarySortDirection,
+        Action::ToggleNew,
+        Action::Stats,
+        Action::ToggleOutliers,
+        Action::CycleBinning,
+        Action::Open,
+        Action::Back,
+        Action::ToggleView,
+        Action::NextColumn,
+        Action::PreviousColumn,
+        Action::WidenColumn,
+        Action::NarrowColumn,
+        Action::ToggleReposts,
+        Action::TogglePreview,
+        Action::ToggleDistance,
+        Action::ShowSeller,
+        Action::PickCategory,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Next => "next",
+            Action::Previous => "previous",
+            Action::Search => "search",
+            Action::Sort => "sort",
+            Action::SortDirection => "sort_direction",
+            Action::SecondarySort => "secondary_sort",
+            Action::SecondarySortDirection => "secondary_sort_direction",
+            Action::ToggleNew => "toggle_new",
+            Action::Stats => "stats",
+            Action::ToggleOutliers => "toggle_outliers",
+            Action::CycleBinning => "cycle_binning",
+            Action::Open => "open",
+            Action::Back => "back",
+            Action::ToggleView => "toggle_view",
+            Action::NextColumn => "next_column",
+            Action::PreviousColumn => "previous_column",
+            Action::WidenColumn => "widen_column",
+            Action::NarrowColumn => "narrow_column",
+            Action::ToggleReposts => "toggle_reposts",
+            Action::TogglePreview => "toggle_preview",
+            Action::ToggleDistance => "toggle_distance",
+            Action::ShowSeller => "show_seller",
+            Action::PickCategory => "pick_category",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Next => &["j", "Down"],
+            Action::Previous => &["k", "Up"],
+            Action::Search => &["/"],
+            Action::Sort => &["s"],
+            Action::SortDirection => &["d"],
+            Action::SecondarySort => &["S"],
+            Action::SecondarySortDirection => &["D"],
+            Action::ToggleNew => &["n"],
+            Action::Stats => &["p"],
+            Action::ToggleOutliers => &["o"],
+            Action::CycleBinning => &["b"],
+            Action::Open => &["Enter"],
+            Action::Back => &["Esc"],
+            Action::ToggleView => &["v"],
+            Action::NextColumn => &["l", "Right"],
+            Action::PreviousColumn => &["h", "Left"],
+            Action::WidenColumn => &["+"],
+            Action::NarrowColumn => &["-"],
+            Action::ToggleReposts => &["r"],
+            Action::TogglePreview => &["i"],
+            Action::ToggleDistance => &["g"],
+            Action::ShowSeller => &["u"],
+            Action::PickCategory => &["c"],
+        }
+    }
+}
+
+/// A single key, optionally combined with Ctrl or Alt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    fn parse(spec: &str) -> Option<KeyBinding> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        loop {
+            if let Some(r) = strip_prefix_ignore_case(rest, "ctrl+") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = r;
+            } else if let Some(r) = strip_prefix_ignore_case(rest, "alt+") {
+                modifiers |= KeyModifiers::ALT;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+
+        let code = match rest.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "space" => KeyCode::Char(' '),
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return None,
+                }
+            }
+        };
+        Some(KeyBinding { code, modifiers })
+    }
+
+    fn matches(&self, key: &KeyEvent) -> bool {
+        // Shift is already reflected in the character itself
+        let modifiers = key.modifiers - KeyModifiers::SHIFT;
+        self.code == key.code && self.modifiers == modifiers
+    }
+
+    fn label(&self) -> String {
+        let key = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            code => format!("{:?}", code),
+        };
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            format!("Ctrl+{}", key)
+        } else if self.modifiers.contains(KeyModifiers::ALT) {
+            format!("Alt+{}", key)
+        } else {
+            key
+        }
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    s.get(..prefix.len())
+        .filter(|head| head.eq_ignore_ascii_case(prefix))
+        .map(|_| &s[prefix.len()..])
+}
+
+/// Maps key presses to actions.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl Keymap {
+    pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.matches(key))
+            .map(|(_, action)| *action)
+    }
+
+    /// Human readable keys bound to an action, e.g. `j/Down`.
+    pub fn label(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(binding, _)| binding.label())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// Colors used throughout the TUI.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub title: Color,
+    pub body: Color,
+    pub highlight: Color,
+    pub error: Color,
+    pub accent: Color,
+    pub chart: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            title: Color::Green,
+            body: Color::Gray,
+            highlight: Color::DarkGray,
+            error: Color::Red,
+            accent: Color::Yellow,
+            chart: Color::Blue,
+        }
+    }
+}
+
+/// Settings loaded from `$XDG_CONFIG_HOME/frutti-cli/config.toml`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub keymap: Keymap,
+    pub theme: Theme,
+    pub default_query: String,
+    pub default_sort: Vec<SortKey>,
+    /// Language of listing pages, also sent to tutti.ch with every search.
+    pub language: Locale,
+    pub network: SearchConfig,
+    /// Queries recorded by `frutti-cli snapshot`.
+    pub saved_queries: Vec<String>,
+    pub image_protocol: ImageProtocol,
+    pub home: Option<Home>,
+    /// Hide listings farther from home than this.
+    pub max_distance_km: Option<f64>,
+    /// Synonyms searched along with each query, empty unless enabled.
+    pub synonyms: Synonyms,
+}
+
+/// Where listings would be picked up from.
+#[derive(Debug, Clone)]
+pub struct Home {
+    pub postcode: String,
+    pub coordinates: Coordinates,
+}
+
+// The file as written by the user, before validation
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    default_query: Option<String>,
+    default_sort: Option<String>,
+    language: Option<String>,
+    saved_queries: Vec<String>,
+    keys: HashMap<String, Vec<String>>,
+    theme: HashMap<String, String>,
+    network: RawNetwork,
+    images: RawImages,
+    location: RawLocation,
+    synonyms: RawSynonyms,
+    cache: RawCache,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawNetwork {
+    timeout_secs: Option<u64>,
+    max_pages: Option<usize>,
+    search_document: Option<PathBuf>,
+    persist_session: Option<bool>,
+    proxy: Option<String>,
+    user_agents: Vec<String>,
+    connect_timeout_secs: Option<u64>,
+    read_timeout_secs: Option<u64>,
+    root_certificates: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawLocation {
+    home_postcode: Option<String>,
+    max_distance_km: Option<f64>,
+    postcodes_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawSynonyms {
+    enabled: bool,
+    builtin: bool,
+    file: Option<PathBuf>,
+}
+
+impl Default for RawSynonyms {
+    fn default() -> Self {
+        RawSynonyms {
+            enabled: false,
+            builtin: true,
+            file: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawCache {
+    enabled: bool,
+    ttl_secs: Option<u64>,
+    stale_secs: Option<u64>,
+    max_mb: Option<u64>,
+    offline: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawImages {
+    protocol: Option<String>,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("frutti-cli").join("config.toml"))
+    }
+
+    /// Default location of the user's own synonyms, next to the config file.
+    pub fn synonyms_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("frutti-cli").join("synonyms.txt"))
+    }
+
+    /// Caches responses for offline use, in `[cache]` if set up there.
+    pub fn go_offline(&mut self) {
+        let cache = self.network.cache.get_or_insert_with(|| {
+            ResponseCache::new(
+                cache_path("responses").unwrap_or_else(|| PathBuf::from("responses")),
+            )
+        });
+        cache.offline = true;
+    }
+
+    /// Loads the config file, falling back to defaults for anything missing or
+    /// invalid. Problems are returned alongside so they can be shown at startup.
+    pub fn load() -> (Config, Vec<String>) {
+        let Some(path) = Config::path() else {
+            return Config::from_raw(RawConfig::default());
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Config::from_raw(RawConfig::default())
+            }
+            Err(e) => {
+                let (config, _) = Config::from_raw(RawConfig::default());
+                return (config, vec![format!("{}: {}", path.display(), e)]);
+            }
+        };
+        match toml::from_str::<RawConfig>(&contents) {
+            Ok(raw) => {
+                let (config, errors) = Config::from_raw(raw);
+                let errors = errors
+                    .into_iter()
+                    .map(|e| format!("{}: {}", path.display(), e))
+                    .collect();
+                (config, errors)
+            }
+            Err(e) => {
+                let (config, _) = Config::from_raw(RawConfig::default());
+                let message = e.message().to_string();
+                (config, vec![format!("{}: {}", path.display(), message)])
+            }
+        }
+    }
+
+    fn from_raw(raw: RawConfig) -> (Config, Vec<String>) {
+        let mut errors = Vec::new();
+
+        let default_query = match raw.default_query {
+            Some(query) if query.trim().is_empty() => {
+                errors.push("default_query cannot be empty".to_string());
+                None
+            }
+            query => query,
+        }
+        .unwrap_or_else(|| "tutti frutti".to_string());
+
+        let default_sort = raw
+            .default_sort
+            .and_then(|spec| {
+                let keys = parse_sort_keys(&spec);
+                if keys.is_none() {
+                    errors.push(format!(
+                        "invalid default_sort '{}', expected up to two comma separated keys like \"seller, price desc\"",
+                        spec
+                    ));
+                }
+                keys
+            })
+            .unwrap_or_else(|| vec![SortKey::new(SortCategory::Default)]);
+
+        let language = raw
+            .language
+            .and_then(|lang| {
+                let locale = Locale::from_code(&lang);
+                if locale.is_none() {
+                    let codes: Vec<&str> = Locale::ALL.iter().map(Locale::code).collect();
+                    errors.push(format!(
+                        "unknown language '{}', expected one of {}",
+                        lang,
+                        codes.join(", ")
+                    ));
+                }
+                locale
+            })
+            .unwrap_or_default();
+
+        let keymap = parse_keymap(raw.keys, &mut errors);
+        let theme = parse_theme(raw.theme, &mut errors);
+
+        if raw.network.max_pages == Some(0) {
+            errors.push("network.max_pages must be at least 1".to_string());
+        }
+        let search_document = raw.network.search_document.and_then(|path| {
+            read_search_document(&path)
+                .map_err(|e| {
+                    errors.push(format!("network.search_document {}: {}", path.display(), e))
+                })
+                .ok()
+        });
+        let mut network = SearchConfig {
+            max_pages: raw.network.max_pages.filter(|&pages| pages > 0),
+            timeout: raw.network.timeout_secs.map(Duration::from_secs),
+            // Applied by the TUI instead, so it can be toggled
+            distance: None,
+            locale: language,
+            search_document,
+            // Set from the command line
+            cassette: None,
+            cache: None,
+            session_file: raw
+                .network
+                .persist_session
+                .unwrap_or(true)
+                .then(|| cache_path("session.json"))
+                .flatten(),
+            client: ClientOptions {
+                proxy: raw.network.proxy,
+                user_agents: raw.network.user_agents,
+                connect_timeout: raw.network.connect_timeout_secs.map(Duration::from_secs),
+                read_timeout: raw.network.read_timeout_secs.map(Duration::from_secs),
+                root_certificates: raw.network.root_certificates,
+            },
+        };
+        for path in &network.client.root_certificates {
+            if !path.is_file() {
+                errors.push(format!(
+                    "network.root_certificates: {} not found",
+                    path.display()
+                ));
+            }
+        }
+        if raw.cache.enabled || raw.cache.offline {
+            match cache_path("responses") {
+                Some(dir) => {
+                    let defaults = ResponseCache::new(dir);
+                    network.cache = Some(ResponseCache {
+                        ttl: raw.cache.ttl_secs.map_or(defaults.ttl, Duration::from_secs),
+                        stale_while_revalidate: raw
+                            .cache
+                            .stale_secs
+                            .map_or(defaults.stale_while_revalidate, Duration::from_secs),
+                        max_bytes: raw
+                            .cache
+                            .max_mb
+                            .map_or(defaults.max_bytes, |mb| mb * 1024 * 1024),
+                        offline: raw.cache.offline,
+                        ..defaults
+                    });
+                }
+                None => errors.push("cache: no cache directory available".to_string()),
+            }
+        }
+
+        let saved_queries = raw
+            .saved_queries
+            .into_iter()
+            .filter(|query| {
+                let valid = !query.trim().is_empty();
+                if !valid {
+                    errors.push("saved_queries cannot contain empty queries".to_string());
+                }
+                valid
+            })
+            .collect();
+
+        let image_protocol = raw
+            .images
+            .protocol
+            .and_then(|name| {
+                let protocol = ImageProtocol::from_name(&name);
+                if protocol.is_none() {
+                    errors.push(format!(
+                        "unknown images.protocol '{}', expected one of {}",
+                        name,
+                        ImageProtocol::NAMES.join(", ")
+                    ));
+                }
+                protocol
+            })
+            .unwrap_or(ImageProtocol::Auto);
+
+        if let Some(path) = raw.location.postcodes_file {
+            if let Err(e) = geo::load_postcode_table(&path) {
+                errors.push(format!("location.postcodes_file {}: {}", path.display(), e));
+            }
+        }
+        let home = raw.location.home_postcode.and_then(|postcode| {
+            let coordinates = geo::postcode_coordinates(&postcode);
+            if coordinates.is_none() {
+                errors.push(format!("unknown location.home_postcode '{}'", postcode));
+            }
+            coordinates.map(|coordinates| Home {
+                postcode: postcode.trim().to_string(),
+                coordinates,
+            })
+        });
+        let max_distance_km = raw.location.max_distance_km.filter(|&km| {
+            let valid = km > 0.0 && km.is_finite();
+            if !valid {
+                errors.push("location.max_distance_km must be positive".to_string());
+            }
+            valid
+        });
+        if max_distance_km.is_some() && home.is_none() {
+            errors
+                .push("location.max_distance_km needs a valid location.home_postcode".to_string());
+        }
+
+        let mut synonyms = Synonyms::default();
+        if raw.synonyms.enabled {
+            if raw.synonyms.builtin {
+                synonyms = Synonyms::builtin();
+            }
+            if let Some(path) = raw.synonyms.file.or_else(Config::synonyms_path) {
+                match Synonyms::load(&path) {
+                    Ok(own) => synonyms.extend(own),
+                    Err(e) => errors.push(format!("synonyms.file {}: {}", path.display(), e)),
+                }
+            }
+        }
+
+        let config = Config {
+            keymap,
+            theme,
+            default_query,
+            default_sort,
+            language,
+            network,
+            saved_queries,
+            image_protocol,
+            home,
+            max_distance_km,
+            synonyms,
+        };
+        (config, errors)
+    }
+}
+
+// A primary and optional secondary key, e.g. "seller, price desc"
+fn parse_sort_keys(spec: &str) -> Option<Vec<SortKey>> {
+    let keys = spec
+        .split(',')
+        .map(SortKey::parse)
+        .collect::<Option<Vec<_>>>()?;
+    let valid = match keys.as_slice() {
+        [_] => true,
+        [primary, secondary] => {
+            primary.category != SortCategory::Default && primary.category != secondary.category
+        }
+        _ => false,
+    };
+    valid.then_some(keys)
+}
+
+fn parse_keymap(mut keys: HashMap<String, Vec<String>>, errors: &mut Vec<String>) -> Keymap {
+    let mut bindings: Vec<(KeyBinding, Action)> = Vec::new();
+    for action in Action::ALL {
+        let specs = keys.remove(action.name()).unwrap_or_else(|| {
+            action
+                .default_keys()
+                .iter()
+                .map(|k| k.to_string())
+                .collect()
+        });
+        for spec in specs {
+            let Some(binding) = KeyBinding::parse(&spec) else {
+                errors.push(format!("invalid key '{}' for keys.{}", spec, action.name()));
+                continue;
+            };
+            if let Some((_, other)) = bindings.iter().find(|(b, _)| *b == binding) {
+                errors.push(format!(
+                    "key '{}' is bound to both keys.{} and keys.{}",
+                    spec,
+                    other.name(),
+                    action.name()
+                ));
+                continue;
+            }
+            bindings.push((binding, action));
+        }
+    }
+    let mut unknown: Vec<_> = keys.into_keys().collect();
+    unknown.sort();
+    for name in unknown {
+        errors.push(format!("unknown action keys.{}", name));
+    }
+    Keymap { bindings }
+}
+
+fn parse_theme(mut colors: HashMap<String, String>, errors: &mut Vec<String>) -> Theme {
+    let mut theme = Theme::default();
+    let slots: [(&str, &mut Color); 6] = [
+        ("title", &mut theme.title),
+        ("body", &mut theme.body),
+        ("highlight", &mut theme.highlight),
+        ("error", &mut theme.error),
+        ("accent", &mut theme.accent),
+        ("chart", &mut theme.chart),
+    ];
+    for (name, slot) in slots {
+        if let Some(value) = colors.remove(name) {
+            match value.parse::<Color>() {
+                Ok(color) => *slot = color,
+                Err(_) => errors.push(format!("invalid color '{}' for theme.{}", value, name)),
+            }
+        }
+    }
+    let mut unknown: Vec<_> = colors.into_keys().collect();
+    unknown.sort();
+    for name in unknown {
+        errors.push(format!("unknown theme color theme.{}", name));
+    }
+    theme
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyEventKind;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            state: crossterm::event::KeyEventState::NONE,
+        }
+    }
+
+    #[test]
+    fn test_defaults_without_config_file() {
+        let (config, errors) = Config::from_raw(RawConfig::default());
+        assert!(errors.is_empty());
+        assert_eq!(config.default_query, "tutti frutti");
+        assert_eq!(config.language, Locale::De);
+        assert_eq!(
+            config
+                .keymap
+                .action_for(&key(KeyCode::Char('q'), KeyModifiers::NONE)),
+            Some(Action::Quit)
+        );
+        assert_eq!(config.keymap.label(Action::Next), "j/Down");
+        assert!(config.synonyms.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_entries_are_reported_and_defaulted() {
+        let raw: RawConfig = toml::from_str(
+            r##"
+            default_sort = "colour"
+            language = "rm"
+
+            [keys]
+            quit = ["Ctrl+c"]
+            next = ["Ctrl+c", "NotAKey"]
+
+            [theme]
+            title = "not-a-color"
+            error = "#ff8800"
+            "##,
+        )
+        .unwrap();
+        let (config, errors) = Config::from_raw(raw);
+
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert_eq!(
+            config.default_sort,
+            vec![SortKey::new(SortCategory::Default)]
+        );
+        assert_eq!(config.language, Locale::De);
+        assert_eq!(config.network.locale, Locale::De);
+        assert_eq!(config.theme.title, Color::Green);
+        assert_eq!(config.theme.error, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(
+            config
+                .keymap
+                .action_for(&key(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            config
+                .keymap
+                .action_for(&key(KeyCode::Char('q'), KeyModifiers::NONE)),
+            None
+        );
+    }
+}
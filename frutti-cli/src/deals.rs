@@ -0,0 +1,171 @@
+use crate::config::{data_path, Config};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use tutti_frutti::deals::{DealScore, DealScorer, DealThresholds, PriceStore};
+use tutti_frutti::dedup::{self, DedupOptions, RepostCluster};
+use tutti_frutti::graphql::ListingNode;
+use tutti_frutti::synonyms::fetch_listings_expanded;
+
+/// Scores every priced listing against the price history of the query, then
+/// adds the listings' prices to it. Reposts in `clusters` are left out of the
+/// history so an item posted several times is only counted once.
+pub fn score_deals(
+    query: &str,
+    listings: &[ListingNode],
+    clusters: &[RepostCluster],
+) -> Result<HashMap<String, DealScore>> {
+    let path = data_path("prices.json").context("No data directory available")?;
+    let mut store =
+        PriceStore::load(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let scores = score_and_record(&mut store, query, listings, clusters);
+    store
+        .save(&path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(scores)
+}
+
+// The history leaves out the listings being scored, including those recorded
+// by earlier searches, so no listing is compared with its own price
+fn score_and_record(
+    store: &mut PriceStore,
+    query: &str,
+    listings: &[ListingNode],
+    clusters: &[RepostCluster],
+) -> HashMap<String, DealScore> {
+    let ids: HashSet<&str> = listings.iter().map(|l| l.listingID.as_str()).collect();
+    let scores = match DealScorer::new(
+        &store.prices_excluding(query, &ids),
+        DealThresholds::default(),
+    ) {
+        Some(scorer) => listings
+            .iter()
+            .filter_map(|l| scorer.score(l).map(|score| (l.listingID.clone(), score)))
+            .collect(),
+        None => HashMap::new(),
+    };
+    store.record(query, &dedup::collapse(listings.to_vec(), clusters));
+    scores
+}
+
+#[derive(Serialize)]
+struct ScoredListing<'a> {
+    listing_id: &'a str,
+    title: &'a str,
+    url: String,
+    /// How often the seller reposted the item, always 0 with `--keep-reposts`.
+    reposts: usize,
+    /// The query and synonym variants that found the listing.
+    matched_queries: &'a [String],
+    #[serde(flatten)]
+    score: &'a DealScore,
+}
+
+/// `frutti-cli deals <query>`: prints the listings of a query, best deals first.
+/// Reposts of the same item are listed once unless `keep_reposts` is set.
+pub async fn print_deals(
+    config: &Config,
+    query: &str,
+    json: bool,
+    keep_reposts: bool,
+) -> Result<()> {
+    let expanded = fetch_listings_expanded(query, &config.network, &config.synonyms)
+        .await
+        .map_err(|e| anyhow::anyhow!("Search error: {}", e))?;
+    for (variant, e) in &expanded.failed {
+        eprintln!("Variant '{}' skipped: {}", variant, e);
+    }
+    let listings = expanded.merged.listings.clone();
+    let clusters = dedup::find_reposts(&listings, &DedupOptions::default());
+    let scores = score_deals(query, &listings, &clusters)?;
+    let (listings, repost_counts) = if keep_reposts {
+        (listings, HashMap::new())
+    } else {
+        (
+            dedup::collapse(listings, &clusters),
+            dedup::repost_counts(&clusters),
+        )
+    };
+
+    let mut scored: Vec<ScoredListing> = listings
+        .iter()
+        .filter_map(|l| {
+            scores.get(&l.listingID).map(|score| ScoredListing {
+                listing_id: &l.listingID,
+                title: &l.title,
+                url: l.url(config.language),
+                reposts: repost_counts.get(&l.listingID).copied().unwrap_or(0),
+                matched_queries: expanded.merged.matched_by(&l.listingID),
+                score,
+            })
+        })
+        .collect();
+    scored.sort_by(|a, b| a.score.percentile_rank.total_cmp(&b.score.percentile_rank));
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&scored)?);
+        return Ok(());
+    }
+
+    if scored.is_empty() {
+        println!(
+            "Not enough priced listings for '{}' to score deals yet.",
+            query
+        );
+        return Ok(());
+    }
+    println!(
+        "{:<17} {:>6} {:>6} {:>12}  Title",
+        "Verdict", "Pctl", "z", "Price"
+    );
+    for listing in &scored {
+        let badge = match listing.reposts {
+            0 => String::new(),
+            reposts => format!(" (reposted {}×)", reposts),
+        };
+        println!(
+            "{:<17} {:>6.1} {:>6.2} {:>12}  {}{}",
+            listing.score.verdict.as_str(),
+            listing.score.percentile_rank,
+            listing.score.z_score,
+            format!("CHF {:.0}", listing.score.price),
+            listing.title,
+            badge
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(id: &str, price: u32) -> ListingNode {
+        ListingNode::test(id)
+            .with_seller(id)
+            .with_price(&format!("{}.-", price))
+    }
+
+    #[test]
+    fn test_own_price_does_not_shift_the_score() {
+        let mut store = PriceStore::default();
+        let history: Vec<ListingNode> = (1..=5)
+            .map(|i| listing(&format!("old{}", i), i * 100))
+            .collect();
+        store.record("velo", &history);
+        let expected = DealScorer::new(&store.prices("velo"), DealThresholds::default())
+            .unwrap()
+            .score_price(5000.0);
+
+        let new = [listing("new", 5000)];
+        let first = score_and_record(&mut store, "velo", &new, &[]);
+        assert_eq!(first["new"], expected);
+        assert_eq!(store.prices("velo").len(), 6);
+        // Seen again, it is still scored against the others only
+        let second = score_and_record(&mut store, "velo", &new, &[]);
+        assert_eq!(second["new"], expected);
+
+        // A first search has no history to score against
+        assert!(score_and_record(&mut PriceStore::default(), "sofa", &history, &[]).is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::config::{data_path, Config};
+use anyhow::{Context, Result};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use tutti_frutti::batch::SearchRequest;
+use tutti_frutti::client::Session;
+use tutti_frutti::dedup::{self, DedupOptions};
+use tutti_frutti::graphql::ListingNode;
+use tutti_frutti::history::{DailySnapshot, PriceHistory, Trend};
+use tutti_frutti::synonyms::search_expanded;
+
+fn history_path() -> Result<PathBuf> {
+    data_path("history.json").context("No data directory available")
+}
+
+fn load() -> Result<(PriceHistory, PathBuf)> {
+    let path = history_path()?;
+    let history =
+        PriceHistory::load(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok((history, path))
+}
+
+/// Records today's snapshot of a query and returns its trend so far. Reposts
+/// should already be collapsed, they would inflate the supply.
+pub fn record_snapshot(query: &str, listings: &[ListingNode]) -> Result<Trend> {
+    let (mut history, path) = load()?;
+    history.record(query, chrono::Local::now().date_naive(), listings);
+    history
+        .save(&path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(history.trend(query))
+}
+
+/// The trend of a query so far, recording nothing.
+pub fn trend(query: &str) -> Result<Trend> {
+    let (history, _) = load()?;
+    Ok(history.trend(query))
+}
+
+/// `frutti-cli snapshot`: records today's snapshot of every saved query, e.g.
+/// from a daily cron job.
+pub async fn snapshot_saved_queries(config: &Config) -> Result<()> {
+    if config.saved_queries.is_empty() {
+        anyhow::bail!("No saved_queries in {}", config_path_hint());
+    }
+    // One handshake for all queries
+    let session = Session::start(&config.network)
+        .await
+        .map_err(|e| anyhow::anyhow!("Search error: {}", e))?;
+    let mut failed = 0;
+    for query in &config.saved_queries {
+        let result = search_expanded(&session, &SearchRequest::new(query), &config.synonyms)
+            .await
+            .map_err(|e| anyhow::anyhow!("Search error: {}", e))
+            .and_then(|expanded| {
+                for (variant, e) in &expanded.failed {
+                    eprintln!("{}: variant '{}' skipped: {}", query, variant, e);
+                }
+                let listings = expanded.merged.listings;
+                let clusters = dedup::find_reposts(&listings, &DedupOptions::default());
+                let unique = dedup::collapse(listings, &clusters);
+                record_snapshot(query, &unique).map(|_| unique.len())
+            });
+        match result {
+            Ok(count) => println!("{}: {} listings", query, count),
+            Err(e) => {
+                eprintln!("{}: {:#}", query, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!(
+            "{} of {} queries failed",
+            failed,
+            config.saved_queries.len()
+        );
+    }
+    Ok(())
+}
+
+fn config_path_hint() -> String {
+    Config::path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "the config file".to_string())
+}
+
+/// `frutti-cli history <query>`: writes the recorded snapshots of a query as CSV.
+pub fn export_csv(query: &str, output: Option<PathBuf>) -> Result<()> {
+    let (history, _) = load()?;
+    let snapshots = history.snapshots(query);
+    if snapshots.is_empty() {
+        anyhow::bail!("No price history recorded for '{}'", query);
+    }
+    match output {
+        Some(path) => {
+            let mut file = std::fs::File::create(&path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            write_csv(&mut file, snapshots)?;
+        }
+        None => write_csv(&mut io::stdout().lock(), snapshots)?,
+    }
+    Ok(())
+}
+
+fn write_csv(out: &mut impl Write, snapshots: &[DailySnapshot]) -> io::Result<()> {
+    writeln!(out, "date,listings,priced,min,q1,median,mean,q3,max")?;
+    for s in snapshots {
+        writeln!(
+            out,
+            "{},{},{},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2}",
+            s.date, s.listings, s.priced, s.min, s.q1, s.median, s.mean, s.q3, s.max
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_layout() {
+        let snapshot = DailySnapshot {
+            date: chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+            listings: 12,
+            priced: 10,
+            min: 50.0,
+            q1: 100.0,
+            median: 150.5,
+            mean: 160.25,
+            q3: 200.0,
+            max: 900.0,
+        };
+        let mut out = Vec::new();
+        write_csv(&mut out, &[snapshot]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "date,listings,priced,min,q1,median,mean,q3,max\n\
+             2024-05-01,12,10,50.00,100.00,150.50,160.25,200.00,900.00\n"
+        );
+    }
+}
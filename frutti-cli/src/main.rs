@@ -1,4 +1,5 @@
 use anyhow::Result;
+use clap::{Parser, Subcommand};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
@@ -6,53 +7,216 @@ use crossterm::{
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
-    Terminal,
+    symbols::Marker,
+    widgets::{Axis, BarChart, Block, Chart, Dataset, GraphType, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, TableState},
+    Frame, Terminal,
 };
-use std::{io, time::Duration};
-use tutti_frutti::{fetch_listings, graphql::ListingNode};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    time::Duration,
+};
+use tutti_frutti::graphql::ListingNode;
+use categories::{CategoryPicker, SelectedCategory};
+use tutti_frutti::batch::SearchRequest;
+use tutti_frutti::cassette::Cassette;
+use tutti_frutti::client::Session;
+use tutti_frutti::errors::FetchListingsError;
+use tutti_frutti::seller::{SellerProfile, SellerRef};
+use tutti_frutti::synonyms::fetch_listings_expanded;
+
+mod categories;
+mod config;
+mod deals;
+mod history;
+mod query;
+mod seen;
+mod sort;
+mod thumbnail;
+
+use config::{Action, Config, Theme};
+use seen::SeenStore;
+use sort::{SortCategory, SortKey};
+use thumbnail::{ImageProtocol, ThumbnailState, Thumbnails};
+use tutti_frutti::analytics::{self, Binning, PriceStats, StatsOptions};
+use tutti_frutti::deals::{DealScore, DealVerdict};
+use tutti_frutti::dedup::{self, DedupOptions, RepostCluster};
+use tutti_frutti::geo::{Coordinates, DistanceFilter};
+use tutti_frutti::history::Trend;
+use tutti_frutti::util::query_key;
+use tutti_frutti::images::ImageCache;
+
+/// Browse tutti.ch search results in the terminal.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Save every exchange with tutti.ch to this file, with cookies and tokens redacted
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<std::path::PathBuf>,
+    /// Answer requests from a file saved with --record instead of tutti.ch
+    #[arg(long, global = true, value_name = "FILE")]
+    replay: Option<std::path::PathBuf>,
+    /// Only show cached responses, without contacting tutti.ch
+    #[arg(long, global = true)]
+    offline: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Score the listings of a query against the prices seen for it so far
+    Deals {
+        query: String,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+        /// List every repost of an item instead of only the newest one
+        #[arg(long)]
+        keep_reposts: bool,
+    },
+    /// Record today's price statistics of every query in saved_queries
+    Snapshot,
+    /// Export the recorded daily price statistics of a query as CSV
+    History {
+        query: String,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// List the categories with the IDs searches can be limited to
+    Categories,
+    /// Run a GraphQL document against the tutti.ch API and print the data as JSON
+    Query {
+        document: std::path::PathBuf,
+        /// Variables of the document as a JSON object
+        #[arg(long)]
+        variables: Option<String>,
+    },
+    /// Report fields of a search response that differ from what frutti-cli expects
+    Diagnose {
+        /// Defaults to default_query
+        query: Option<String>,
+    },
+}
+
+// How the results are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ViewMode {
+    List,
+    Table,
+}
 
-// Define an enum for sort categories
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SortCategory {
-    Default,
+enum Column {
     Title,
     Price,
     Seller,
+    Age,
+    Location,
+    Distance,
 }
 
-impl SortCategory {
-    fn next(&self) -> Self {
+impl Column {
+    const ALL: [Column; 6] = [
+        Column::Title,
+        Column::Price,
+        Column::Seller,
+        Column::Age,
+        Column::Location,
+        Column::Distance,
+    ];
+
+    fn header(&self) -> &'static str {
         match self {
-            SortCategory::Default => SortCategory::Title,
-            SortCategory::Title => SortCategory::Price,
-            SortCategory::Price => SortCategory::Seller,
-            SortCategory::Seller => SortCategory::Default,
+            Column::Title => "Title",
+            Column::Price => "Price",
+            Column::Seller => "Seller",
+            Column::Age => "Age",
+            Column::Location => "Location",
+            Column::Distance => "Distance",
         }
     }
-    
-    fn as_str(&self) -> &'static str {
+
+    fn default_width(&self) -> u16 {
+        match self {
+            Column::Title => 48,
+            Column::Price => 14,
+            Column::Seller => 18,
+            Column::Age => 6,
+            Column::Location => 22,
+            Column::Distance => 10,
+        }
+    }
+
+    fn sort_category(&self) -> SortCategory {
+        match self {
+            Column::Title => SortCategory::Title,
+            Column::Price => SortCategory::Price,
+            Column::Seller => SortCategory::Seller,
+            Column::Age => SortCategory::Date,
+            Column::Location => SortCategory::Location,
+            Column::Distance => SortCategory::Distance,
+        }
+    }
+
+    fn cell(&self, listing: &ListingNode, home: Option<&Coordinates>) -> String {
         match self {
-            SortCategory::Default => "Default",
-            SortCategory::Title => "Title",
-            SortCategory::Price => "Price",
-            SortCategory::Seller => "Seller",
+            Column::Title => listing.title.clone(),
+            Column::Price => listing.formattedPrice.clone().unwrap_or_default(),
+            Column::Seller => listing.sellerInfo.alias.clone(),
+            Column::Age => format_age(listing),
+            Column::Location => listing.location().unwrap_or_default(),
+            Column::Distance => format_distance(listing, home).unwrap_or_default(),
         }
     }
 }
 
-// New struct to store price statistics
-struct PriceStats {
-    count: usize,
-    min: f64,
-    max: f64,
-    mean: f64,
-    median: f64,
-    histogram: Vec<usize>,
-    bin_width: f64,
+// Distance from home such as "12 km", if both are known
+fn format_distance(listing: &ListingNode, home: Option<&Coordinates>) -> Option<String> {
+    home.and_then(|home| listing.distance_km(home))
+        .map(|km| format!("{:.0} km", km))
+}
+
+// Short relative age such as "5m", "3h" or "12d"
+fn format_age(listing: &ListingNode) -> String {
+    let Some(posted_at) = listing.posted_at() else {
+        return String::from("?");
+    };
+    let age = chrono::Utc::now().signed_duration_since(posted_at);
+    if age.num_days() > 0 {
+        format!("{}d", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h", age.num_hours())
+    } else {
+        format!("{}m", age.num_minutes().max(0))
+    }
+}
+
+// The listings of one seller, shown instead of the search results until Back
+struct SellerView {
+    profile: SellerProfile,
+    results: Vec<ListingNode>,  // Search results to return to
+    repost_clusters: Vec<RepostCluster>,  // Of the search results
+    selected: Option<String>,  // Listing the seller was opened from
+}
+
+impl SellerView {
+    // E.g. "member since 2016, 31 online, 8001 Zürich"
+    fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(date) = self.profile.member_since_date() {
+            parts.push(format!("member since {}", date.format("%Y")));
+        }
+        parts.push(format!("{} online", self.profile.listing_count));
+        if let Some(location) = &self.profile.location {
+            parts.push(format!("{} {}", location.postcode, location.locationName));
+        }
+        parts.join(", ")
+    }
 }
 
 struct App {
@@ -63,22 +227,90 @@ struct App {
     search_mode: bool,
     loading: bool,
     error: Option<String>,
-    sort_category: SortCategory,
+    status: Option<String>,  // Non-fatal problems, shown in the help bar title
+    sort_keys: Vec<SortKey>,  // Primary key first, then an optional secondary key
     stats_mode: bool,  // New field to track stats mode
+    exclude_outliers: bool,
+    binning: Binning,
+    view_mode: ViewMode,
+    table_state: TableState,
+    selected_column: usize,
+    column_widths: [u16; 6],
+    seen: SeenStore,
+    results_query: String,  // Query that produced the current results
+    matched_queries: HashMap<String, Vec<String>>,  // Query and synonym variants that found each listing
+    variant_count: usize,  // Synonym variants searched besides results_query
+    new_ids: HashSet<String>,  // Listings unseen before the last search
+    deal_scores: HashMap<String, DealScore>,
+    trend: Trend,  // Daily price history of results_query
+    show_only_new: bool,
+    repost_clusters: Vec<RepostCluster>,
+    repost_counts: HashMap<String, usize>,  // Reposts by the listing kept for each cluster
+    collapse_reposts: bool,
+    distance_filter: Option<DistanceFilter>,  // From the config, applied while nearby_only is set
+    nearby_only: bool,
+    seller_view: Option<SellerView>,
+    seller_session: Option<Session>,  // Started by the first seller drill-in, then reused
+    category: Option<SelectedCategory>,  // Searches are limited to this category
+    category_picker: Option<CategoryPicker>,  // Open while picking a category
+    thumbnails: Thumbnails,
+    show_preview: bool,
+    image_protocol: ImageProtocol,  // Resolved from the config, never Auto
+    graphics_target: Option<(String, Rect)>,  // Picture the last frame wants drawn with a graphics protocol
+    graphics_shown: Option<(String, Rect)>,  // Picture currently on screen
+    config: Config,
+    config_errors: Vec<String>,  // Shown once at startup until a key is pressed
 }
 
 impl App {
-    fn new() -> App {
+    fn new(config: Config, config_errors: Vec<String>) -> App {
+        let thumbnail_dir = config::cache_path("thumbnails")
+            .unwrap_or_else(|| std::env::temp_dir().join("frutti-cli-thumbnails"));
         App {
             listings: Vec::new(),
             original_listings: Vec::new(),
             list_state: ListState::default(),
-            search_query: String::from("tutti frutti"),
+            search_query: config.default_query.clone(),
             search_mode: false,
             loading: false,
             error: None,
-            sort_category: SortCategory::Default,
+            status: None,
+            sort_keys: config.default_sort.clone(),
             stats_mode: false,
+            exclude_outliers: false,
+            binning: Binning::Linear,
+            view_mode: ViewMode::List,
+            table_state: TableState::default(),
+            selected_column: 0,
+            column_widths: Column::ALL.map(|c| c.default_width()),
+            seen: SeenStore::load(),
+            results_query: String::new(),
+            matched_queries: HashMap::new(),
+            variant_count: 0,
+            new_ids: HashSet::new(),
+            deal_scores: HashMap::new(),
+            trend: Trend::default(),
+            show_only_new: false,
+            repost_clusters: Vec::new(),
+            repost_counts: HashMap::new(),
+            collapse_reposts: true,
+            distance_filter: config.home.as_ref().zip(config.max_distance_km).map(|(home, max_km)| DistanceFilter {
+                home: home.coordinates,
+                max_km,
+                keep_unknown: true,
+            }),
+            nearby_only: config.max_distance_km.is_some(),
+            seller_view: None,
+            seller_session: None,
+            category: None,
+            category_picker: None,
+            thumbnails: Thumbnails::new(ImageCache::with_config(thumbnail_dir, &config.network).map_err(|e| e.to_string())),
+            show_preview: true,
+            image_protocol: config.image_protocol.resolve(),
+            graphics_target: None,
+            graphics_shown: None,
+            config,
+            config_errors,
         }
     }
 
@@ -94,7 +326,7 @@ impl App {
             None if !self.listings.is_empty() => 0,
             None => return,
         };
-        self.list_state.select(Some(i));
+        self.select(Some(i));
     }
 
     fn previous(&mut self) {
@@ -109,11 +341,219 @@ impl App {
             None if !self.listings.is_empty() => 0,
             None => return,
         };
-        self.list_state.select(Some(i));
+        self.select(Some(i));
+    }
+
+    // Select a listing the user navigated to and remember that it has been viewed
+    fn select(&mut self, index: Option<usize>) {
+        self.list_state.select(index);
+        self.mark_selected_seen();
+    }
+
+    // Browsing a seller's listings leaves the query's seen listings alone
+    fn mark_selected_seen(&mut self) {
+        if self.seller_view.is_some() {
+            return;
+        }
+        if let Some(listing) = self.list_state.selected().and_then(|i| self.listings.get(i)) {
+            self.seen.mark_seen(&self.results_query, &listing.listingID);
+        }
+    }
+
+    fn is_new(&self, listing: &ListingNode) -> bool {
+        self.new_ids.contains(&listing.listingID)
+    }
+
+    fn toggle_show_only_new(&mut self) {
+        self.show_only_new = !self.show_only_new;
+        self.sort_listings();
+    }
+
+    fn toggle_collapse_reposts(&mut self) {
+        self.collapse_reposts = !self.collapse_reposts;
+        self.sort_listings();
+    }
+
+    // Listings from the API in their original order, minus those hidden by filters
+    fn visible_listings(&self) -> Vec<ListingNode> {
+        let listings = self
+            .original_listings
+            .iter()
+            .filter(|l| !self.show_only_new || self.seller_view.is_some() || self.is_new(l))
+            .filter(|l| match &self.distance_filter {
+                Some(filter) if self.nearby_only => filter.matches(l),
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        if self.collapse_reposts {
+            dedup::collapse(listings, &self.repost_clusters)
+        } else {
+            listings
+        }
+    }
+
+    fn home(&self) -> Option<&Coordinates> {
+        self.config.home.as_ref().map(|home| &home.coordinates)
+    }
+
+    fn toggle_nearby_only(&mut self) {
+        if self.distance_filter.is_some() {
+            self.nearby_only = !self.nearby_only;
+            self.sort_listings();
+        }
+    }
+
+    fn selected_listing(&self) -> Option<&ListingNode> {
+        self.list_state.selected().and_then(|i| self.listings.get(i))
+    }
+
+    fn hidden_reposts(&self) -> usize {
+        if self.collapse_reposts {
+            self.repost_counts.values().sum()
+        } else {
+            0
+        }
+    }
+
+    // Replaces the results with all listings of the selected listing's seller
+    async fn show_seller(&mut self) {
+        let Some(listing) = self.selected_listing() else {
+            return;
+        };
+        let Some(seller) = listing.seller_ref() else {
+            self.status = Some("Listing has no seller alias".to_string());
+            return;
+        };
+        let selected = listing.listingID.clone();
+        self.loading = true;
+        match self.seller_listings(&seller).await {
+            Ok((profile, listings)) => {
+                let repost_clusters = dedup::find_reposts(&listings, &DedupOptions::default());
+                self.seller_view = Some(SellerView {
+                    profile,
+                    results: std::mem::replace(&mut self.original_listings, listings),
+                    repost_clusters: std::mem::replace(&mut self.repost_clusters, repost_clusters),
+                    selected: Some(selected),
+                });
+                self.repost_counts = dedup::repost_counts(&self.repost_clusters);
+                self.list_state.select(None);
+                self.sort_listings();
+            }
+            Err(e) => self.status = Some(format!("Seller unavailable: {}", e)),
+        }
+        self.loading = false;
+    }
+
+    // Seller drill-ins share one session instead of making a handshake each
+    async fn seller_listings(&mut self, seller: &SellerRef) -> Result<(SellerProfile, Vec<ListingNode>), FetchListingsError> {
+        let session = match &self.seller_session {
+            Some(session) => session.clone(),
+            None => self.seller_session.insert(Session::start(&self.config.network).await?).clone(),
+        };
+        session.seller_listings(seller).await
+    }
+
+    fn close_seller(&mut self) {
+        let Some(view) = self.seller_view.take() else {
+            return;
+        };
+        self.original_listings = view.results;
+        self.repost_clusters = view.repost_clusters;
+        self.repost_counts = dedup::repost_counts(&self.repost_clusters);
+        self.sort_listings();
+        if let Some(i) = view.selected.and_then(|id| self.listings.iter().position(|l| l.listingID == id)) {
+            self.select(Some(i));
+        }
+    }
+
+    fn save_seen(&mut self) {
+        if let Err(e) = self.seen.save() {
+            self.error = Some(format!("Failed to save seen listings: {}", e));
+        }
     }
 
     fn toggle_sort(&mut self) {
-        self.sort_category = self.sort_category.next();
+        let category = self.sort_keys[0].category.next();
+        self.set_primary_sort(category);
+        self.sort_listings();
+    }
+
+    fn set_primary_sort(&mut self, category: SortCategory) {
+        self.sort_keys[0] = SortKey::new(category);
+        // A secondary key is meaningless after the unique API order or the same key
+        if category == SortCategory::Default || self.sort_keys.get(1).is_some_and(|k| k.category == category) {
+            self.sort_keys.truncate(1);
+        }
+    }
+
+    fn toggle_sort_direction(&mut self) {
+        self.sort_keys[0].toggle_direction();
+        self.sort_listings();
+    }
+
+    // Cycle the secondary key through every category other than the primary one
+    fn cycle_secondary_sort(&mut self) {
+        let primary = self.sort_keys[0].category;
+        if primary == SortCategory::Default {
+            return;
+        }
+        let mut next = self.sort_keys.get(1).map_or(SortCategory::Default, |k| k.category).next();
+        if next == primary {
+            next = next.next();
+        }
+        self.sort_keys.truncate(1);
+        if next != SortCategory::Default {
+            self.sort_keys.push(SortKey::new(next));
+        }
+        self.sort_listings();
+    }
+
+    fn toggle_secondary_sort_direction(&mut self) {
+        if let Some(key) = self.sort_keys.get_mut(1) {
+            key.toggle_direction();
+            self.sort_listings();
+        }
+    }
+
+    fn sort_description(&self) -> String {
+        self.sort_keys.iter().map(|k| k.label()).collect::<Vec<_>>().join(", then ")
+    }
+
+    fn toggle_view_mode(&mut self) {
+        self.view_mode = match self.view_mode {
+            ViewMode::List => ViewMode::Table,
+            ViewMode::Table => ViewMode::List,
+        };
+    }
+
+    fn move_column(&mut self, forward: bool) {
+        let count = Column::ALL.len();
+        self.selected_column = if forward {
+            (self.selected_column + 1) % count
+        } else {
+            (self.selected_column + count - 1) % count
+        };
+    }
+
+    fn resize_column(&mut self, delta: i16) {
+        let width = &mut self.column_widths[self.selected_column];
+        *width = width.saturating_add_signed(delta).clamp(4, 120);
+    }
+
+    // Sort by the selected table column, flipping the direction if it is already
+    // sorted by it. The previous column becomes the secondary key.
+    fn sort_by_column(&mut self) {
+        let category = Column::ALL[self.selected_column].sort_category();
+        let previous = self.sort_keys[0];
+        if previous.category == category {
+            self.sort_keys[0].toggle_direction();
+        } else {
+            self.sort_keys = vec![SortKey::new(category)];
+            if previous.category != SortCategory::Default {
+                self.sort_keys.push(previous);
+            }
+        }
         self.sort_listings();
     }
 
@@ -124,93 +564,90 @@ impl App {
             self.listings.get(i).map(|item| item.listingID.clone())
         );
         
-        match self.sort_category {
-            SortCategory::Default => {
-                // Restore original order from API
-                self.listings = self.original_listings.clone();
-            },
-            SortCategory::Title => {
-                self.listings.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()));
-            },
-            SortCategory::Price => {
-                // Parse prices and sort numerically
-                self.listings.sort_by(|a, b| {
-                    let parse_price = |price: &Option<String>| -> Option<f64> {
-                        price
-                            .as_ref()
-                            .and_then(|p| {
-                                // More robust price parsing (same as stats function)
-                                let sanitized = p.chars()
-                                    .map(|c| match c {
-                                        '0'..='9' => c,
-                                        '.' | ',' => '.', // Convert both . and , to decimal point
-                                        _ => ' '          // Replace all other chars with spaces
-                                    })
-                                    .collect::<String>();
-                                
-                                // Remove all spaces and parse
-                                sanitized.replace(' ', "").parse::<f64>().ok()
-                            })
-                    };
-                    
-                    let price_a = parse_price(&a.formattedPrice);
-                    let price_b = parse_price(&b.formattedPrice);
-                    
-                    match (price_a, price_b) {
-                        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
-                        (Some(_), None) => std::cmp::Ordering::Less,
-                        (None, Some(_)) => std::cmp::Ordering::Greater,
-                        (None, None) => std::cmp::Ordering::Equal,
-                    }
-                });
-            },
-            SortCategory::Seller => {
-                self.listings.sort_by(|a, b| {
-                    a.sellerInfo.alias.to_lowercase().cmp(&b.sellerInfo.alias.to_lowercase())
-                });
-            }
-        }
+        // Start from the original order from API so filter changes take effect
+        self.listings = sort::sort_listings(self.visible_listings(), &self.sort_keys, self.home());
 
         // Restore selection after sorting
-        if let Some(id) = selected_id {
-            if let Some(new_index) = self.listings.iter().position(|item| item.listingID == id) {
-                self.list_state.select(Some(new_index));
-            } else if !self.listings.is_empty() {
-                self.list_state.select(Some(0));
-            }
+        let new_index = selected_id
+            .and_then(|id| self.listings.iter().position(|item| item.listingID == id));
+        match new_index {
+            Some(i) => self.list_state.select(Some(i)),
+            // Not viewed yet, so the listing stays new until the user moves to it
+            None if !self.listings.is_empty() => self.list_state.select(Some(0)),
+            None => self.list_state.select(None),
+        }
+    }
+
+    // Key of the current query and category in the seen, price and history
+    // stores, e.g. "velo [Fahrzeuge › Velos]"
+    fn search_key(&self, query: &str) -> String {
+        match &self.category {
+            Some(category) if query.trim().is_empty() => format!("[{}]", category.label),
+            Some(category) => format!("{} [{}]", query.trim(), category.label),
+            None => query.to_string(),
         }
     }
 
     async fn search(&mut self, query: &str) -> Result<()> {
-        // Validate query before searching
-        if query.trim().is_empty() {
+        // Validate query before searching, a category can be browsed without one
+        if query.trim().is_empty() && self.category.is_none() {
             self.error = Some("Search query cannot be empty".to_string());
             return Ok(());
         }
+        let mut request = SearchRequest::new(query.trim());
+        request.category = self.category.as_ref().map(|c| c.id.clone());
+        let key = self.search_key(query);
 
         self.loading = true;
         self.error = None;
+        self.status = None;
+        self.seller_view = None;
+        self.save_seen();
         
         // Use a safer error-handling approach
-        let result = match fetch_listings(query).await {
-            Ok(listings) => {
-                self.original_listings = listings.clone();  // Store original order
-                self.listings = listings;
-                if !self.listings.is_empty() {
-                    self.list_state.select(Some(0));
-                    // Apply current sort if not default
-                    if self.sort_category != SortCategory::Default {
-                        self.sort_listings();
-                    }
-                } else {
-                    self.list_state.select(None);
+        let result = match fetch_listings_expanded(request, &self.config.network, &self.config.synonyms).await {
+            Ok(expanded) => {
+                let listings = expanded.merged.listings;
+                self.results_query = key.clone();
+                self.matched_queries = expanded.merged.matched;
+                self.variant_count = self.config.synonyms.variants(query).len().saturating_sub(expanded.failed.len());
+                if let Some((variant, e)) = expanded.failed.first() {
+                    self.status = Some(format!("{} synonym searches failed, e.g. '{}': {}", expanded.failed.len(), variant, e));
                 }
+                self.new_ids = listings
+                    .iter()
+                    .filter(|l| !self.seen.is_seen(&key, &l.listingID))
+                    .map(|l| l.listingID.clone())
+                    .collect();
+                // Reposts would count the same item several times in the price history
+                self.repost_clusters = dedup::find_reposts(&listings, &DedupOptions::default());
+                self.repost_counts = dedup::repost_counts(&self.repost_clusters);
+                self.deal_scores = deals::score_deals(&key, &listings, &self.repost_clusters).unwrap_or_else(|e| {
+                    self.status = Some(format!("Deal scoring unavailable: {:#}", e));
+                    HashMap::new()
+                });
+                let unique = dedup::collapse(listings.clone(), &self.repost_clusters);
+                // Only saved queries get snapshots, so one-off searches and typos stay out of the history
+                let trend = if self.config.saved_queries.iter().any(|saved| query_key(saved) == query_key(&key)) {
+                    history::record_snapshot(&key, &unique)
+                } else {
+                    history::trend(&key)
+                };
+                self.trend = trend.unwrap_or_else(|e| {
+                    self.status = Some(format!("Price history unavailable: {:#}", e));
+                    Trend::default()
+                });
+                self.original_listings = listings;  // Store original order
+                self.list_state.select(None);
+                self.sort_listings();
                 Ok(())
             }
             Err(e) => {
                 self.error = Some(format!("Search error: {}", e));
                 self.listings = Vec::new();
                 self.original_listings = Vec::new();
+                self.matched_queries.clear();
+                self.variant_count = 0;
                 self.list_state.select(None);
                 Ok(())
             }
@@ -221,10 +658,11 @@ impl App {
     }
 
     // Add a new function to construct and open the listing URL
-    fn open_selected_listing(&self) -> Result<()> {
+    fn open_selected_listing(&mut self) -> Result<()> {
+        self.mark_selected_seen();
         if let Some(selected) = self.list_state.selected() {
             if let Some(listing) = self.listings.get(selected) {
-                let url = format!("https://www.tutti.ch/de/vi/{}", listing.listingID);
+                let url = listing.url(self.config.language);
                 println!("Opening: {}", url);
                 open::that(url)?;
             }
@@ -239,111 +677,26 @@ impl App {
 
     // Function to calculate price statistics
     fn calculate_price_stats(&self) -> PriceStats {
-        // Extract prices and convert to numbers
-        let mut prices: Vec<f64> = self.listings.iter()
-            .filter_map(|listing| {
-                listing.formattedPrice
-                    .as_ref()
-                    .and_then(|p| {
-                        // More robust price parsing
-                        // First, normalize to numeric characters and decimal point
-                        let sanitized = p.chars()
-                            .map(|c| match c {
-                                '0'..='9' => c,
-                                '.' | ',' => '.', // Convert both . and , to decimal point
-                                _ => ' '          // Replace all other chars with spaces
-                            })
-                            .collect::<String>();
-                        
-                        // Remove all spaces
-                        let cleaned = sanitized.replace(' ', "");
-                        
-                        // Try to parse as f64
-                        cleaned.parse::<f64>().ok()
-                    })
-            })
-            .collect();
-        
-        // Handle empty case
-        if prices.is_empty() {
-            return PriceStats {
-                count: 0,
-                min: 0.0,
-                max: 0.0,
-                mean: 0.0,
-                median: 0.0,
-                histogram: vec![0; 10],
-                bin_width: 0.0,
-            };
-        }
-        
-        // Sort prices for median calculation
-        prices.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        
-        let count = prices.len();
-        let min = prices.first().cloned().unwrap_or(0.0);
-        let max = prices.last().cloned().unwrap_or(0.0);
-        let sum: f64 = prices.iter().sum();
-        let mean = if count > 0 { sum / count as f64 } else { 0.0 };
-        
-        // Calculate median
-        let median = if count > 0 {
-            if count % 2 == 0 {
-                (prices[count / 2 - 1] + prices[count / 2]) / 2.0
-            } else {
-                prices[count / 2]
-            }
-        } else {
-            0.0
+        let options = StatsOptions {
+            binning: self.binning,
+            // Adaptive binning picks its own count, allow it more room
+            bins: if self.binning == Binning::Adaptive { 30 } else { 10 },
+            exclude_outliers: self.exclude_outliers,
+            ..StatsOptions::default()
+        };
+        PriceStats::from_listings(&self.listings, &options)
+    }
+
+    fn toggle_outliers(&mut self) {
+        self.exclude_outliers = !self.exclude_outliers;
+    }
+
+    fn cycle_binning(&mut self) {
+        self.binning = match self.binning {
+            Binning::Linear => Binning::Log,
+            Binning::Log => Binning::Adaptive,
+            Binning::Adaptive => Binning::Linear,
         };
-        
-        // Create histogram with 10 bins
-        let mut histogram = vec![0; 10];
-        if count > 0 && max > min {
-            // Calculate bin width
-            let bin_width = (max - min) / 10.0;
-            
-            // Create explicit bin boundaries for more accurate distribution
-            let bin_boundaries: Vec<f64> = (0..10)
-                .map(|i| min + (i as f64 * bin_width))
-                .collect();
-            
-            // Assign each price to a bin
-            for price in prices.iter() {
-                // Find the appropriate bin
-                let mut bin_idx = 9; // Default to last bin
-                for (i, boundary) in bin_boundaries.iter().enumerate() {
-                    let upper_bound = if i < 9 { bin_boundaries[i + 1] } else { max + 0.01 }; // Add small value to include max
-                    if *price >= *boundary && *price < upper_bound {
-                        bin_idx = i;
-                        break;
-                    }
-                }
-                histogram[bin_idx] += 1;
-            }
-            
-            return PriceStats {
-                count,
-                min,
-                max,
-                mean,
-                median,
-                histogram,
-                bin_width,
-            };
-        } else {
-            // If all prices are the same
-            histogram[0] = count;
-            return PriceStats {
-                count,
-                min,
-                max,
-                mean,
-                median,
-                histogram,
-                bin_width: 1.0,
-            };
-        }
     }
 }
 
@@ -355,7 +708,7 @@ fn truncate_to_char_boundary(s: &str, max_chars: usize) -> &str {
 
     let mut char_indices = s.char_indices();
     for _ in 0..max_chars {
-        if let None = char_indices.next() {
+        if char_indices.next().is_none() {
             return s; // String is shorter than max_chars
         }
     }
@@ -369,62 +722,453 @@ fn truncate_to_char_boundary(s: &str, max_chars: usize) -> &str {
 }
 
 // Helper function to render price statistics
-fn render_price_stats(stats: &PriceStats) -> Paragraph {
+fn render_price_stats<'a>(stats: &PriceStats, binning: Binning, theme: &Theme, width: u16) -> Paragraph<'a> {
+    let outlier_note = if stats.outliers > 0 {
+        format!(" ({} outliers excluded)", stats.outliers)
+    } else {
+        String::new()
+    };
     let mut lines = vec![
         Line::from(vec![
-            Span::styled("Price Statistics", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            Span::styled("Price Statistics", Style::default().fg(theme.title).add_modifier(Modifier::BOLD)),
         ]),
         Line::from(vec![
-            Span::raw(format!("Count: {} items with price information", stats.count)),
+            Span::raw(format!("Count: {} items with price information{}", stats.count, outlier_note)),
         ]),
         Line::from(vec![
             Span::raw(format!("Range: CHF {:.2} - CHF {:.2}", stats.min, stats.max)),
         ]),
         Line::from(vec![
-            Span::raw(format!("Average: CHF {:.2}", stats.mean)),
+            Span::raw(format!("Average: CHF {:.2} | Trimmed (10%): CHF {:.2} | Std Dev: CHF {:.2}",
+                stats.mean, stats.trimmed_mean, stats.std_dev)),
         ]),
         Line::from(vec![
             Span::raw(format!("Median: CHF {:.2}", stats.median)),
         ]),
         Line::from(vec![
-            Span::styled("Price Distribution:", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("P10: CHF {:.0} | Q1: CHF {:.0} | Q3: CHF {:.0} | P90: CHF {:.0}",
+                stats.p10, stats.q1, stats.q3, stats.p90)),
         ]),
     ];
-    
-    // Skip histogram if no data
-    if stats.count > 0 {
+
+    // Skip box plot and histogram if no data
+    if stats.count == 0 {
+        lines.push(Line::from("No price data available"));
+    } else {
+        let plot_width = width.saturating_sub(4).clamp(10, 60) as usize;
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![
+            Span::styled("Box Plot:", Style::default().add_modifier(Modifier::BOLD)),
+        ]));
+        lines.push(Line::from(vec![
+            Span::styled(box_plot(stats, plot_width), Style::default().fg(theme.chart)),
+        ]));
+        lines.push(Line::from(format!("{:<w$}{:>w2$}",
+            format!("CHF {:.0}", stats.min),
+            format!("CHF {:.0}", stats.max),
+            w = plot_width / 2,
+            w2 = plot_width - plot_width / 2)));
+
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![
+            Span::styled(format!("Price Distribution ({} bins):", binning), Style::default().add_modifier(Modifier::BOLD)),
+        ]));
+
         // Find the maximum count in the histogram for scaling
-        let max_count = *stats.histogram.iter().max().unwrap_or(&1);
-        
+        let max_count = stats.histogram.iter().map(|bin| bin.count).max().unwrap_or(1).max(1);
+        let bar_space = width.saturating_sub(30).clamp(10, 40) as f64;
+
         // Add histogram bars
-        for (i, &count) in stats.histogram.iter().enumerate() {
-            let bin_start = stats.min + i as f64 * stats.bin_width;
-            let bin_end = bin_start + stats.bin_width;
-            
-            let bin_label = format!("CHF {:.0}-{:.0}", bin_start, bin_end);
-            let percent = count as f64 / max_count as f64;
-            
+        for bin in &stats.histogram {
+            let bin_label = format!("CHF {:.0}-{:.0}", bin.start, bin.end);
+            let percent = bin.count as f64 / max_count as f64;
+
             // Create a bar using Unicode block characters
-            let bar_width = (40.0 * percent).round() as usize;
+            let bar_width = (bar_space * percent).round() as usize;
             let bar = "█".repeat(bar_width);
-            
+
             lines.push(Line::from(vec![
-                Span::raw(format!("{:<15} ", bin_label)),
-                Span::styled(bar, Style::default().fg(Color::Blue)),
-                Span::raw(format!(" {}", count)),
+                Span::raw(format!("{:<17} ", bin_label)),
+                Span::styled(bar, Style::default().fg(theme.chart)),
+                Span::raw(format!(" {}", bin.count)),
             ]));
         }
-    } else {
-        lines.push(Line::from("No price data available"));
     }
-    
+
     Paragraph::new(lines)
         .block(Block::default().borders(Borders::ALL).title("Price Statistics"))
         .wrap(ratatui::widgets::Wrap { trim: false })
 }
 
+// Text box plot: whiskers from min to max, box from Q1 to Q3, median marked
+fn box_plot(stats: &PriceStats, width: usize) -> String {
+    let span = stats.max - stats.min;
+    let position = |value: f64| -> usize {
+        if span <= 0.0 {
+            0
+        } else {
+            (((value - stats.min) / span) * (width - 1) as f64).round() as usize
+        }
+    };
+    let (q1, median, q3) = (position(stats.q1), position(stats.median), position(stats.q3));
+
+    (0..width)
+        .map(|i| {
+            if i == median {
+                '┃'
+            } else if i == 0 {
+                '├'
+            } else if i == width - 1 {
+                '┤'
+            } else if i == q1 {
+                '['
+            } else if i == q3 {
+                ']'
+            } else if i > q1 && i < q3 {
+                '█'
+            } else {
+                '─'
+            }
+        })
+        .collect()
+}
+
+fn render_stats_view(f: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.config.theme;
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+        .split(sections[0]);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(columns[1]);
+
+    let stats = app.calculate_price_stats();
+    let stats_view = render_price_stats(&stats, app.binning, theme, columns[0].width);
+    f.render_widget(stats_view, columns[0]);
+
+    // Most active sellers
+    let header = Row::new(["Seller", "Listings", "Avg Price"])
+        .style(Style::default().fg(theme.title).add_modifier(Modifier::BOLD));
+    let rows = analytics::seller_stats(&app.listings).into_iter().map(|seller| {
+        Row::new([
+            seller.alias,
+            seller.count.to_string(),
+            seller.mean_price.map(|p| format!("CHF {:.0}", p)).unwrap_or_else(|| "-".to_string()),
+        ])
+    });
+    let sellers = Table::new(rows, [Constraint::Min(12), Constraint::Length(9), Constraint::Length(12)])
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title("Sellers"));
+    f.render_widget(sellers, right[0]);
+
+    // Listings per day, as many recent days as fit
+    let bar_width = 5;
+    let visible_days = (right[1].width.saturating_sub(2) / (bar_width + 1)).max(1) as usize;
+    let per_day = analytics::listings_per_day(&app.listings);
+    let recent = &per_day[per_day.len().saturating_sub(visible_days)..];
+    let labels: Vec<String> = recent.iter().map(|day| day.date.format("%d.%m").to_string()).collect();
+    let data: Vec<(&str, u64)> = labels
+        .iter()
+        .zip(recent)
+        .map(|(label, day)| (label.as_str(), day.count as u64))
+        .collect();
+    let per_day_chart = BarChart::default()
+        .block(Block::default().borders(Borders::ALL).title("Listings per Day"))
+        .data(&data)
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(theme.chart))
+        .value_style(Style::default().fg(Color::Black).bg(theme.chart));
+    f.render_widget(per_day_chart, right[1]);
+
+    render_trend(f, &app.trend, theme, sections[1]);
+}
+
+// Median price and number of listings of the current query, one point per
+// day a search was recorded
+fn render_trend(f: &mut Frame, trend: &Trend, theme: &Theme, area: Rect) {
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+
+    let median_title = match trend.median_change {
+        Some(change) => format!("Median Price ({:+.1}%)", change * 100.0),
+        None => "Median Price".to_string(),
+    };
+    let supply: Vec<_> = trend.supply.iter().map(|&(date, count)| (date, count as f64)).collect();
+    let series = [(median_title, &trend.median, "CHF"), ("Listings".to_string(), &supply, "")];
+
+    for ((title, points, unit), area) in series.iter().zip(halves.iter()) {
+        let block = Block::default().borders(Borders::ALL).title(title.as_str());
+        let (Some(&(first_day, _)), Some(&(last_day, _))) = (points.first(), points.last()) else {
+            f.render_widget(Paragraph::new("No history yet").block(block), *area);
+            continue;
+        };
+        if first_day == last_day {
+            let message = "Search again on another day to see a trend";
+            f.render_widget(Paragraph::new(message).block(block), *area);
+            continue;
+        }
+
+        let data: Vec<(f64, f64)> = points
+            .iter()
+            .map(|(date, value)| ((*date - first_day).num_days() as f64, *value))
+            .collect();
+        let low = data.iter().map(|(_, y)| *y).fold(f64::INFINITY, f64::min);
+        let high = data.iter().map(|(_, y)| *y).fold(f64::NEG_INFINITY, f64::max);
+        // Leave some room so a flat line does not sit on the border
+        let margin = ((high - low) * 0.1).max(1.0);
+        let (low, high) = ((low - margin).max(0.0), high + margin);
+
+        let dataset = Dataset::default()
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(theme.chart))
+            .data(&data);
+        let chart = Chart::new(vec![dataset])
+            .block(block)
+            .x_axis(Axis::default()
+                .bounds([0.0, (last_day - first_day).num_days() as f64])
+                .labels(vec![
+                    Span::raw(first_day.format("%d.%m.%y").to_string()),
+                    Span::raw(last_day.format("%d.%m.%y").to_string()),
+                ]))
+            .y_axis(Axis::default()
+                .bounds([low, high])
+                .labels(vec![
+                    Span::raw(format!("{} {:.0}", unit, low).trim().to_string()),
+                    Span::raw(format!("{} {:.0}", unit, high).trim().to_string()),
+                ]));
+        f.render_widget(chart, *area);
+    }
+}
+
+// Price line of a list item, followed by the deal verdict if the listing was scored
+fn price_line<'a>(text: String, score: Option<&DealScore>, theme: &Theme) -> Vec<Span<'a>> {
+    let mut spans = vec![Span::raw(text)];
+    if let Some(score) = score {
+        let color = match score.verdict {
+            DealVerdict::SuspiciouslyLow => theme.error,
+            DealVerdict::Bargain => theme.title,
+            DealVerdict::Fair => theme.body,
+            DealVerdict::Expensive => theme.accent,
+        };
+        spans.push(Span::raw(" | "));
+        spans.push(Span::styled(
+            format!("{} (P{:.0}, z {:+.1})", score.verdict.as_str(), score.percentile_rank, score.z_score),
+            Style::default().fg(color),
+        ));
+    }
+    spans
+}
+
+// Compact one-row-per-listing view with a header for sorting by column
+fn render_listing_table(f: &mut Frame, app: &mut App, area: Rect, block: Block) {
+    let theme = &app.config.theme;
+    let header = Row::new(Column::ALL.iter().enumerate().map(|(i, column)| {
+        let mut label = column.header().to_string();
+        // Show the primary and secondary sort columns with their direction
+        for (rank, key) in app.sort_keys.iter().enumerate() {
+            if key.category == column.sort_category() {
+                label.push(' ');
+                label.push_str(key.arrow());
+                if rank > 0 {
+                    label.push('²');
+                }
+            }
+        }
+        let mut style = Style::default().fg(theme.title).add_modifier(Modifier::BOLD);
+        if i == app.selected_column {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        Cell::from(label).style(style)
+    }));
+
+    let rows = app.listings.iter().map(|l| {
+        let cells = Column::ALL.iter().map(|column| {
+            let mut text = column.cell(l, app.home());
+            if *column == Column::Title {
+                if let Some(reposts) = app.repost_counts.get(&l.listingID) {
+                    text = format!("[reposted {}×] {}", reposts, text);
+                }
+            }
+            let cell = Cell::from(text);
+            if *column == Column::Title && app.is_new(l) {
+                cell.style(Style::default().fg(theme.accent))
+            } else {
+                cell
+            }
+        });
+        Row::new(cells)
+    });
+
+    let widths = app.column_widths.map(Constraint::Length);
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .highlight_style(Style::default().bg(theme.highlight))
+        .highlight_symbol("> ");
+
+    app.table_state.select(app.list_state.selected());
+    f.render_stateful_widget(table, area, &mut app.table_state);
+    render_scrollbar(f, area, app.listings.len(), app.list_state.selected(), theme);
+}
+
+// Details of the selected listing: its thumbnail, then the full description
+fn render_preview(f: &mut Frame, app: &mut App, area: Rect) {
+    let theme = &app.config.theme;
+    let block = Block::default().borders(Borders::ALL).title("Details");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let Some(listing) = app.selected_listing() else {
+        return;
+    };
+
+    // Picture at the top, at most half of the pane
+    let max_rows = (inner.height / 2).min(16);
+    let mut picture_rows = 0;
+    let mut graphics_target = None;
+    if app.image_protocol != ImageProtocol::Off && max_rows > 0 {
+        let failed;
+        let message = match app.thumbnails.get(&listing.listingID) {
+            Some(ThumbnailState::Ready(_, decoded)) => {
+                let (cols, rows) = thumbnail::fit(decoded, inner.width, max_rows);
+                let picture_area = Rect { height: rows, width: cols, ..inner };
+                if app.image_protocol.is_graphics() {
+                    graphics_target = Some((listing.listingID.clone(), picture_area));
+                } else {
+                    f.render_widget(Paragraph::new(thumbnail::halfblocks(decoded, cols, rows)), picture_area);
+                }
+                picture_rows = rows;
+                None
+            }
+            Some(ThumbnailState::Loading) => Some("Loading picture..."),
+            Some(ThumbnailState::Failed(e)) => {
+                failed = format!("Picture unavailable: {}", e);
+                Some(failed.as_str())
+            }
+            None => Some("No picture"),
+        };
+        if let Some(message) = message {
+            f.render_widget(Paragraph::new(message).style(Style::default().fg(theme.body)), Rect { height: 1, ..inner });
+            picture_rows = 1;
+        }
+        picture_rows += 1;
+    }
+
+    let mut lines = vec![
+        Line::from(Span::styled(listing.title.clone(), Style::default().fg(theme.title).add_modifier(Modifier::BOLD))),
+        Line::from(format!("Price: {}", listing.formattedPrice.as_deref().unwrap_or("No price"))),
+        Line::from(format!("Seller: {}", listing.sellerInfo.alias)),
+    ];
+    if let Some(location) = listing.location() {
+        match format_distance(listing, app.home()) {
+            Some(distance) => lines.push(Line::from(format!("Location: {} ({} away)", location, distance))),
+            None => lines.push(Line::from(format!("Location: {}", location))),
+        }
+    }
+    lines.push(Line::from(format!("Posted: {} ago", format_age(listing))));
+    if app.variant_count > 0 {
+        if let Some(matched) = app.matched_queries.get(&listing.listingID) {
+            lines.push(Line::from(format!("Matched: {}", matched.join(", "))));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(listing.body.clone(), Style::default().fg(theme.body))));
+
+    let text_area = Rect {
+        y: inner.y + picture_rows.min(inner.height),
+        height: inner.height.saturating_sub(picture_rows),
+        ..inner
+    };
+    let details = Paragraph::new(lines).wrap(ratatui::widgets::Wrap { trim: false });
+    f.render_widget(details, text_area);
+    app.graphics_target = graphics_target;
+}
+
+fn render_scrollbar(f: &mut Frame, area: Rect, total_items: usize, selected: Option<usize>, theme: &Theme) {
+    use ratatui::widgets::{Scrollbar, ScrollbarOrientation, ScrollbarState};
+
+    if total_items == 0 {
+        return;
+    }
+
+    // Get inner height excluding the block borders
+    let inner_height = area.height.saturating_sub(2);
+
+    let mut scrollbar_state = ScrollbarState::new(total_items)
+        .position(selected.unwrap_or(0));
+
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(Some("↑"))
+        .end_symbol(Some("↓"))
+        .track_symbol(Some("│"))
+        .thumb_symbol("█")
+        .track_style(Style::default().fg(theme.highlight))
+        .thumb_style(Style::default().fg(Color::White));
+
+    // Calculate scrollbar area (position it on the right edge of the list area)
+    let scrollbar_area = Rect {
+        x: area.x + area.width - 2, // Put it on the right edge
+        y: area.y + 1, // Skip the border
+        width: 1,
+        height: inner_height,
+    };
+
+    f.render_stateful_widget(scrollbar, scrollbar_area, &mut scrollbar_state);
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let (mut config, config_errors) = Config::load();
+    let recording = cli.record.map(Cassette::record);
+    config.network.cassette = recording.clone();
+    if let Some(path) = cli.replay {
+        let cassette = Cassette::replay(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to load {}: {}", path.display(), e))?;
+        config.network.cassette = Some(cassette);
+    }
+    if cli.offline {
+        config.go_offline();
+    }
+
+    let result = run(cli.command, config, config_errors).await;
+    // The recording is written once at the end rather than after every exchange
+    if let Some(cassette) = recording {
+        cassette.save().map_err(|e| anyhow::anyhow!("Failed to save {}: {}", cassette.path().display(), e))?;
+    }
+    result
+}
+
+async fn run(command: Option<Command>, config: Config, config_errors: Vec<String>) -> Result<()> {
+    // The TUI shows config problems itself, subcommands print them up front
+    let Some(command) = command else {
+        return run_tui(config, config_errors).await;
+    };
+    for error in &config_errors {
+        eprintln!("Config: {}", error);
+    }
+    match command {
+        Command::Deals { query, json, keep_reposts } => {
+            deals::print_deals(&config, &query, json, keep_reposts).await
+        }
+        Command::Snapshot => history::snapshot_saved_queries(&config).await,
+        Command::History { query, output } => history::export_csv(&query, output),
+        Command::Categories => categories::print_tree(&config).await,
+        Command::Query { document, variables } => query::run_document(&config, &document, variables.as_deref()).await,
+        Command::Diagnose { query } => query::diagnose(&config, query).await,
+    }
+}
+
+async fn run_tui(config: Config, config_errors: Vec<String>) -> Result<()> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -433,7 +1177,7 @@ async fn main() -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
 
     // Create app state
-    let mut app = App::new();
+    let mut app = App::new(config, config_errors);
     
     // Initial search - FIX: Clone the query first
     let initial_query = app.search_query.clone();
@@ -441,6 +1185,14 @@ async fn main() -> Result<()> {
 
     // Main loop
     loop {
+        app.thumbnails.poll();
+        if app.show_preview && app.view_mode == ViewMode::List && app.image_protocol != ImageProtocol::Off {
+            if let Some(listing) = app.selected_listing().cloned() {
+                app.thumbnails.request(&listing);
+            }
+        }
+
+        app.graphics_target = None;
         terminal.draw(|f| {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
@@ -454,46 +1206,85 @@ async fn main() -> Result<()> {
 
             // Search bar
             let search_style = if app.search_mode {
-                Style::default().fg(Color::Yellow)
+                Style::default().fg(app.config.theme.accent)
             } else {
                 Style::default()
             };
             
             let search_text = if app.search_mode {
-                format!("{}", app.search_query)
+                app.search_query.clone()
             } else {
                 format!("{} (press / to edit)", app.search_query)
             };
             
+            let search_title = match &app.category {
+                Some(category) => format!("Search in {}", category.label),
+                None => "Search".to_string(),
+            };
             let search_bar = Paragraph::new(search_text)
                 .style(search_style)
-                .block(Block::default().borders(Borders::ALL).title("Search"));
+                .block(Block::default().borders(Borders::ALL).title(search_title));
             
             f.render_widget(search_bar, chunks[0]);
 
             // Results area or stats view
+            let mut title_notes = match app.hidden_reposts() {
+                0 => String::new(),
+                hidden => format!(", {} reposts hidden", hidden),
+            };
+            if app.variant_count > 0 && app.seller_view.is_none() {
+                title_notes.push_str(&format!(", +{} synonyms", app.variant_count));
+            }
+            if let (Some(filter), Some(home)) = (app.distance_filter.as_ref().filter(|_| app.nearby_only), &app.config.home) {
+                title_notes.push_str(&format!(", within {} km of {}", filter.max_km, home.postcode));
+                let unknown = app.listings.iter().filter(|l| l.coordinates().is_none()).count();
+                if unknown > 0 {
+                    title_notes.push_str(&format!(", {} with unknown location", unknown));
+                }
+            }
             let results_block = Block::default()
                 .borders(Borders::ALL)
-                .title(format!("Results ({})", app.listings.len()));
+                .title(if let Some(view) = &app.seller_view {
+                    format!("Listings by {} ({}{}) | Sort: {}", view.profile.alias, view.summary(), title_notes, app.sort_description())
+                } else if app.show_only_new {
+                    format!("Results ({}, {} new{}, showing new only) | Sort: {}", app.listings.len(), app.new_ids.len(), title_notes, app.sort_description())
+                } else {
+                    format!("Results ({}, {} new{}) | Sort: {}", app.listings.len(), app.new_ids.len(), title_notes, app.sort_description())
+                });
 
-            if app.loading {
+            if !app.config_errors.is_empty() {
+                let mut lines = vec![Line::from("Problems in the config file, using defaults for these settings:")];
+                lines.extend(app.config_errors.iter().map(|e| Line::from(format!("  {}", e))));
+                lines.push(Line::from(""));
+                lines.push(Line::from("Press any key to continue."));
+                let config_errors = Paragraph::new(lines)
+                    .style(Style::default().fg(app.config.theme.error))
+                    .block(results_block)
+                    .wrap(ratatui::widgets::Wrap { trim: false });
+                f.render_widget(config_errors, chunks[1]);
+            } else if app.loading {
                 let loading = Paragraph::new("Loading...")
                     .block(results_block);
                 f.render_widget(loading, chunks[1]);
             } else if let Some(ref error) = app.error {
                 let error_text = Paragraph::new(error.as_str())
-                    .style(Style::default().fg(Color::Red))
+                    .style(Style::default().fg(app.config.theme.error))
                     .block(results_block);
                 f.render_widget(error_text, chunks[1]);
             } else if app.listings.is_empty() {
-                let empty = Paragraph::new("No results found.")
+                let message = if app.show_only_new && !app.original_listings.is_empty() {
+                    "No new listings since the last search."
+                } else {
+                    "No results found."
+                };
+                let empty = Paragraph::new(message)
                     .block(results_block);
                 f.render_widget(empty, chunks[1]);
             } else if app.stats_mode {
                 // Show price stats when in stats mode
-                let stats = app.calculate_price_stats();
-                let stats_view = render_price_stats(&stats);
-                f.render_widget(stats_view, chunks[1]);
+                render_stats_view(f, &app, chunks[1]);
+            } else if app.view_mode == ViewMode::Table {
+                render_listing_table(f, &mut app, chunks[1], results_block);
             } else {
                 let items: Vec<ListItem> = app
                     .listings
@@ -506,15 +1297,27 @@ async fn main() -> Result<()> {
                         let truncated_body = truncate_to_char_boundary(&l.body, 50);
                         let ellipsis = if truncated_body.len() < l.body.len() { "..." } else { "" };
                         
+                        // Mark listings that were not seen before this search
+                        let mut title_line = Vec::new();
+                        if app.is_new(l) {
+                            title_line.push(Span::styled("NEW ", Style::default().fg(app.config.theme.accent).add_modifier(Modifier::BOLD)));
+                        }
+                        title_line.push(Span::styled(&l.title, Style::default().fg(app.config.theme.title).add_modifier(Modifier::BOLD)));
+                        if let Some(reposts) = app.repost_counts.get(&l.listingID) {
+                            title_line.push(Span::styled(format!(" reposted {}×", reposts), Style::default().fg(app.config.theme.accent)));
+                        }
+
                         ListItem::new(vec![
+                            Line::from(title_line),
+                            Line::from(price_line(
+                                match format_distance(l, app.home()) {
+                                    Some(distance) => format!("Price: {} | Seller: {} | {}", price, seller, distance),
+                                    None => format!("Price: {} | Seller: {}", price, seller),
+                                },
+                                // Scores belong to the query, not to a seller's other listings
+                                app.deal_scores.get(&l.listingID).filter(|_| app.seller_view.is_none()), &app.config.theme)),
                             Line::from(vec![
-                                Span::styled(&l.title, Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-                            ]),
-                            Line::from(vec![
-                                Span::raw(format!("Price: {} | Seller: {}", price, seller)),
-                            ]),
-                            Line::from(vec![
-                                Span::styled(truncated_body, Style::default().fg(Color::Gray)),
+                                Span::styled(truncated_body, Style::default().fg(app.config.theme.body)),
                                 Span::raw(ellipsis),
                             ]),
                         ])
@@ -523,44 +1326,24 @@ async fn main() -> Result<()> {
 
                 let listings = List::new(items)
                     .block(results_block)
-                    .highlight_style(Style::default().bg(Color::DarkGray))
+                    .highlight_style(Style::default().bg(app.config.theme.highlight))
                     .highlight_symbol("> ");
 
-                // First render the list widget
-                let list_area = chunks[1];
+                // First render the list widget, with the details of the selected listing next to it
+                let (list_area, preview_area) = if app.show_preview {
+                    let halves = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                        .split(chunks[1]);
+                    (halves[0], Some(halves[1]))
+                } else {
+                    (chunks[1], None)
+                };
                 f.render_stateful_widget(listings, list_area, &mut app.list_state);
                 
-                // Then create and render a scrollbar
-                // We need to calculate where to place the scrollbar
-                if !app.listings.is_empty() {
-                    use ratatui::widgets::{Scrollbar, ScrollbarOrientation, ScrollbarState};
-                    
-                    // Get inner height excluding the block borders
-                    let inner_height = list_area.height.saturating_sub(2);
-                    
-                    // Create scrollbar state with proper type conversions
-                    let total_items = app.listings.len(); // This is already usize
-                    let position = app.list_state.selected().unwrap_or(0); // This is already usize
-                    let scrollbar_state = ScrollbarState::new(total_items)
-                        .position(position);
-                    
-                    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
-                        .begin_symbol(Some("↑"))
-                        .end_symbol(Some("↓"))
-                        .track_symbol(Some("│"))
-                        .thumb_symbol("█")
-                        .track_style(Style::default().fg(Color::DarkGray))
-                        .thumb_style(Style::default().fg(Color::White));
-                    
-                    // Calculate scrollbar area (position it on the right edge of the list area)
-                    let scrollbar_area = ratatui::layout::Rect {
-                        x: list_area.x + list_area.width - 2, // Put it on the right edge
-                        y: list_area.y + 1, // Skip the border
-                        width: 1,
-                        height: inner_height,
-                    };
-                    
-                    f.render_stateful_widget(scrollbar, scrollbar_area, &mut scrollbar_state.clone());
+                render_scrollbar(f, list_area, app.listings.len(), app.list_state.selected(), &app.config.theme);
+                if let Some(preview_area) = preview_area {
+                    render_preview(f, &mut app, preview_area);
                 }
             }
 
@@ -568,28 +1351,124 @@ async fn main() -> Result<()> {
             let help_text = if app.search_mode {
                 String::from("Enter: Submit Search | Esc: Cancel")
             } else if app.stats_mode {
-                String::from("q: Quit | Esc/p: Back to Listings")
+                let keys = &app.config.keymap;
+                format!("{}: Quit | {}/{}: Back to Listings | {}: Outliers ({}) | {}: Binning ({})",
+                    keys.label(Action::Quit), keys.label(Action::Back), keys.label(Action::Stats),
+                    keys.label(Action::ToggleOutliers),
+                    if app.exclude_outliers { "excluded" } else { "included" },
+                    keys.label(Action::CycleBinning),
+                    app.binning)
             } else {
-                format!("q: Quit | j/Down: Next | k/Up: Previous | /: Search | s: Sort ({}) | p: Price Stats | Enter: Open",
-                    app.sort_category.as_str())
+                let keys = &app.config.keymap;
+                let view_help = match app.view_mode {
+                    ViewMode::List => format!("{}: Sort | {}: Table View | {}: Preview ({})",
+                        keys.label(Action::Sort),
+                        keys.label(Action::ToggleView),
+                        keys.label(Action::TogglePreview),
+                        if app.show_preview { "on" } else { "off" }),
+                    ViewMode::Table => format!("{}/{}: Column | {}/{}: Width | {}: Sort Column | {}: List View",
+                        keys.label(Action::PreviousColumn),
+                        keys.label(Action::NextColumn),
+                        keys.label(Action::NarrowColumn),
+                        keys.label(Action::WidenColumn),
+                        keys.label(Action::Sort),
+                        keys.label(Action::ToggleView)),
+                };
+                format!("{}: Quit | {}: Next | {}: Previous | {}: Search | {} | {}: Direction | {}/{}: Then By/Direction | {}: New Only ({}) | {}: Reposts ({}){} | {} | {}: Category | {}: Price Stats | {}: Open",
+                    keys.label(Action::Quit),
+                    keys.label(Action::Next),
+                    keys.label(Action::Previous),
+                    keys.label(Action::Search),
+                    view_help,
+                    keys.label(Action::SortDirection),
+                    keys.label(Action::SecondarySort),
+                    keys.label(Action::SecondarySortDirection),
+                    keys.label(Action::ToggleNew),
+                    if app.show_only_new { "on" } else { "off" },
+                    keys.label(Action::ToggleReposts),
+                    if app.collapse_reposts { "collapsed" } else { "shown" },
+                    match &app.distance_filter {
+                        Some(filter) => format!(" | {}: Within {} km ({})", keys.label(Action::ToggleDistance), filter.max_km, if app.nearby_only { "on" } else { "off" }),
+                        None => String::new(),
+                    },
+                    if app.seller_view.is_some() {
+                        format!("{}: Back to Results", keys.label(Action::Back))
+                    } else {
+                        format!("{}: Seller", keys.label(Action::ShowSeller))
+                    },
+                    keys.label(Action::PickCategory),
+                    keys.label(Action::Stats),
+                    keys.label(Action::Open))
             };
             
+            let help_title = match &app.status {
+                Some(status) => Line::from(vec![
+                    Span::raw("Help | "),
+                    Span::styled(status.as_str(), Style::default().fg(app.config.theme.error)),
+                ]),
+                None => Line::from("Help"),
+            };
             let help_bar = Paragraph::new(help_text)
-                .block(Block::default().borders(Borders::ALL).title("Help"));
+                .block(Block::default().borders(Borders::ALL).title(help_title));
             
             f.render_widget(help_bar, chunks[2]);
+
+            if let Some(picker) = &mut app.category_picker {
+                picker.render(f, f.size(), &app.config.theme);
+                // Pictures drawn with a graphics protocol would cover the popup
+                app.graphics_target = None;
+            }
         })?;
 
+        // Pictures drawn with a graphics protocol live outside ratatui's buffer,
+        // so clear the screen before replacing them and draw them after each frame
+        if app.graphics_target != app.graphics_shown {
+            if app.graphics_shown.take().is_some() {
+                thumbnail::clear_graphics(terminal.backend_mut(), app.image_protocol)?;
+                terminal.clear()?;
+                continue;
+            }
+            if let Some((listing_id, area)) = &app.graphics_target {
+                if let Some(ThumbnailState::Ready(image, decoded)) = app.thumbnails.get(listing_id) {
+                    thumbnail::draw_graphics(terminal.backend_mut(), app.image_protocol, image, decoded, *area)?;
+                }
+            }
+            app.graphics_shown = app.graphics_target.clone();
+        }
+
         // Handle input
         if event::poll(Duration::from_millis(100))? {
             if let Event::Key(key) = event::read()? {
-                if app.search_mode {
+                if !app.config_errors.is_empty() {
+                    app.config_errors.clear();
+                } else if let Some(picker) = &mut app.category_picker {
+                    match app.config.keymap.action_for(&key) {
+                        Some(Action::Next) => picker.next(),
+                        Some(Action::Previous) => picker.previous(),
+                        Some(Action::Open) => {
+                            app.category = picker.selected();
+                            app.category_picker = None;
+                            // Browse the whole category, a query can narrow it down afterwards
+                            if app.category.is_some() {
+                                app.search_query.clear();
+                            }
+                            if app.category.is_some() || !app.search_query.trim().is_empty() {
+                                let query = app.search_query.clone();
+                                app.search(&query).await?;
+                            }
+                        }
+                        Some(Action::Back | Action::Quit | Action::PickCategory) => {
+                            app.category_picker = None;
+                        }
+                        _ => {}
+                    }
+                } else if app.search_mode {
                     match key.code {
                         KeyCode::Enter => {
                             app.search_mode = false;
                             let query = app.search_query.clone();
-                            // Only search if query isn't empty
-                            if !query.trim().is_empty() {
+                            // Only search if query isn't empty, unless browsing a category
+                            if !query.trim().is_empty() || app.category.is_some() {
                                 match app.search(&query).await {
                                     Ok(_) => {},
                                     Err(e) => {
@@ -616,36 +1495,87 @@ async fn main() -> Result<()> {
                         _ => {}
                     }
                 } else {
-                    match key.code {
-                        KeyCode::Char('q') => {
+                    match app.config.keymap.action_for(&key) {
+                        Some(Action::Quit) => {
                             break;
                         }
-                        KeyCode::Char('j') | KeyCode::Down => {
-                            if !app.stats_mode {
-                                app.next();
-                            }
+                        Some(Action::Next) if !app.stats_mode => {
+                            app.next();
                         }
-                        KeyCode::Char('k') | KeyCode::Up => {
-                            if !app.stats_mode {
-                                app.previous();
-                            }
+                        Some(Action::Previous) if !app.stats_mode => {
+                            app.previous();
                         }
-                        KeyCode::Char('p') => {
+                        Some(Action::Stats) => {
                             app.toggle_stats_mode();
                         }
-                        KeyCode::Esc => {
-                            if app.stats_mode {
-                                app.stats_mode = false;
-                            }
+                        Some(Action::Back) if app.stats_mode => {
+                            app.stats_mode = false;
+                        }
+                        Some(Action::ToggleOutliers) if app.stats_mode => {
+                            app.toggle_outliers();
                         }
-                        KeyCode::Char('/') => {
+                        Some(Action::CycleBinning) if app.stats_mode => {
+                            app.cycle_binning();
+                        }
+                        Some(Action::Search) => {
                             app.search_mode = true;
                             app.search_query.clear();
                         }
-                        KeyCode::Char('s') => {
+                        Some(Action::Sort) if app.view_mode == ViewMode::Table => {
+                            app.sort_by_column();
+                        }
+                        Some(Action::Sort) => {
                             app.toggle_sort();
                         }
-                        KeyCode::Enter => {
+                        Some(Action::SortDirection) => {
+                            app.toggle_sort_direction();
+                        }
+                        Some(Action::SecondarySort) => {
+                            app.cycle_secondary_sort();
+                        }
+                        Some(Action::SecondarySortDirection) => {
+                            app.toggle_secondary_sort_direction();
+                        }
+                        Some(Action::ToggleView) => {
+                            app.toggle_view_mode();
+                        }
+                        Some(Action::NextColumn) if app.view_mode == ViewMode::Table => {
+                            app.move_column(true);
+                        }
+                        Some(Action::PreviousColumn) if app.view_mode == ViewMode::Table => {
+                            app.move_column(false);
+                        }
+                        Some(Action::WidenColumn) if app.view_mode == ViewMode::Table => {
+                            app.resize_column(2);
+                        }
+                        Some(Action::NarrowColumn) if app.view_mode == ViewMode::Table => {
+                            app.resize_column(-2);
+                        }
+                        Some(Action::ToggleNew) => {
+                            app.toggle_show_only_new();
+                        }
+                        Some(Action::ToggleReposts) => {
+                            app.toggle_collapse_reposts();
+                        }
+                        Some(Action::TogglePreview) => {
+                            app.show_preview = !app.show_preview;
+                        }
+                        Some(Action::ToggleDistance) => {
+                            app.toggle_nearby_only();
+                        }
+                        Some(Action::PickCategory) => {
+                            match categories::load(&app.config).await {
+                                Ok(tree) => app.category_picker = Some(CategoryPicker::new(&tree, app.category.as_ref())),
+                                Err(e) => app.status = Some(format!("{:#}", e)),
+                            }
+                        }
+                        Some(Action::ShowSeller) if app.seller_view.is_none() => {
+                            app.show_seller().await;
+                        }
+                        Some(Action::Back) if app.seller_view.is_some() => {
+                            app.close_seller();
+                        }
+                        Some(Action::Open) => {
                             // Open the selected listing in browser when Enter is pressed
                             if let Err(e) = app.open_selected_listing() {
                                 app.error = Some(format!("Failed to open browser: {}", e));
@@ -658,7 +1588,11 @@ async fn main() -> Result<()> {
         }
     }
 
+    // Persist viewed listings for the next run
+    app.save_seen();
+
     // Restore terminal
+    thumbnail::clear_graphics(terminal.backend_mut(), app.image_protocol)?;
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -0,0 +1,44 @@
+use crate::config::Config;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use tutti_frutti::batch::SearchRequest;
+use tutti_frutti::client::Session;
+
+/// `frutti-cli query`: runs a GraphQL document from a file and prints the
+/// `data` of the response as JSON.
+pub async fn run_document(config: &Config, document: &Path, variables: Option<&str>) -> Result<()> {
+    let document = fs::read_to_string(document)
+        .with_context(|| format!("Failed to read {}", document.display()))?;
+    let variables = match variables {
+        Some(variables) => serde_json::from_str(variables).context("Invalid --variables")?,
+        None => serde_json::json!({}),
+    };
+    let session = Session::start(&config.network)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to start session: {}", e))?;
+    let data: serde_json::Value = session
+        .execute(&document, variables)
+        .await
+        .map_err(|e| anyhow::anyhow!("Query failed: {}", e))?;
+    println!("{}", serde_json::to_string_pretty(&data)?);
+    Ok(())
+}
+
+/// `frutti-cli diagnose`: compares a search response with the fields the
+/// library expects, e.g. after searches start failing.
+pub async fn diagnose(config: &Config, query: Option<String>) -> Result<()> {
+    let query = query.unwrap_or_else(|| config.default_query.clone());
+    let session = Session::start(&config.network)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to start session: {}", e))?;
+    let report = session
+        .diagnose(&SearchRequest::new(query.as_str()))
+        .await
+        .map_err(|e| anyhow::anyhow!("Search for '{}' failed: {}", query, e))?;
+    print!("{}", report);
+    if !report.is_ok() {
+        anyhow::bail!("The response differs from the model");
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use crate::config::data_path;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use tutti_frutti::util::query_key;
+
+/// Listing IDs the user has already looked at, grouped by search query.
+///
+/// The store lives in `$XDG_DATA_HOME/frutti-cli/seen.json` so that re-running
+/// the same query can tell which listings are new since the last visit.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SeenStore {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    dirty: bool,
+    queries: HashMap<String, HashSet<String>>,
+}
+
+impl SeenStore {
+    /// Loads the store from the default location, starting empty if the file
+    /// is missing or unreadable.
+    pub fn load() -> Self {
+        let path = data_path("seen.json");
+        let mut store = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str::<SeenStore>(&contents).ok())
+            .unwrap_or_default();
+        store.path = path;
+        store
+    }
+
+    pub fn is_seen(&self, query: &str, listing_id: &str) -> bool {
+        self.queries
+            .get(&query_key(query))
+            .is_some_and(|ids| ids.contains(listing_id))
+    }
+
+    pub fn mark_seen(&mut self, query: &str, listing_id: &str) {
+        let inserted = self
+            .queries
+            .entry(query_key(query))
+            .or_default()
+            .insert(listing_id.to_string());
+        self.dirty |= inserted;
+    }
+
+    /// Writes the store back to disk if anything changed since the last save.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = self.path.as_ref().filter(|_| self.dirty) else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use std::cmp::Ordering;
+use tutti_frutti::geo::Coordinates;
+use tutti_frutti::graphql::ListingNode;
+
+// Define an enum for sort categories
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortCategory {
+    Default,
+    Title,
+    Price,
+    Seller,
+    Date,
+    Location,
+    Distance,
+}
+
+impl SortCategory {
+    const ALL: [SortCategory; 7] = [
+        SortCategory::Default,
+        SortCategory::Title,
+        SortCategory::Price,
+        SortCategory::Seller,
+        SortCategory::Date,
+        SortCategory::Location,
+        SortCategory::Distance,
+    ];
+
+    pub fn next(&self) -> Self {
+        let index = SortCategory::ALL
+            .iter()
+            .position(|c| c == self)
+            .unwrap_or(0);
+        SortCategory::ALL[(index + 1) % SortCategory::ALL.len()]
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "default" => Some(SortCategory::Default),
+            "title" => Some(SortCategory::Title),
+            "price" => Some(SortCategory::Price),
+            "seller" => Some(SortCategory::Seller),
+            "date" | "age" => Some(SortCategory::Date),
+            "location" => Some(SortCategory::Location),
+            "distance" => Some(SortCategory::Distance),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortCategory::Default => "Default",
+            SortCategory::Title => "Title",
+            SortCategory::Price => "Price",
+            SortCategory::Seller => "Seller",
+            SortCategory::Date => "Date",
+            SortCategory::Location => "Location",
+            SortCategory::Distance => "Distance",
+        }
+    }
+
+    // What ascending and descending mean for this category
+    fn direction_labels(&self) -> (&'static str, &'static str) {
+        match self {
+            SortCategory::Default => ("API order", "reversed"),
+            SortCategory::Price => ("lowest first", "highest first"),
+            SortCategory::Date => ("newest first", "oldest first"),
+            SortCategory::Distance => ("nearest first", "farthest first"),
+            SortCategory::Title | SortCategory::Seller | SortCategory::Location => ("A-Z", "Z-A"),
+        }
+    }
+}
+
+/// A sort category together with its direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub category: SortCategory,
+    pub descending: bool,
+}
+
+impl SortKey {
+    pub fn new(category: SortCategory) -> Self {
+        SortKey {
+            category,
+            descending: false,
+        }
+    }
+
+    /// Parses a key such as `price` or `date desc`.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut words = spec.split_whitespace();
+        let category = SortCategory::from_name(words.next()?)?;
+        let descending = match words.next().map(|w| w.to_lowercase()).as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(_) => return None,
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(SortKey {
+            category,
+            descending,
+        })
+    }
+
+    pub fn toggle_direction(&mut self) {
+        self.descending = !self.descending;
+    }
+
+    /// E.g. "Price (highest first)".
+    pub fn label(&self) -> String {
+        let (ascending, descending) = self.category.direction_labels();
+        let direction = if self.descending {
+            descending
+        } else {
+            ascending
+        };
+        format!("{} ({})", self.category.as_str(), direction)
+    }
+
+    pub fn arrow(&self) -> &'static str {
+        if self.descending {
+            "▼"
+        } else {
+            "▲"
+        }
+    }
+}
+
+/// Sorts listings by each key in turn, falling back to the next key on ties.
+/// The listings are expected in API order, which `SortCategory::Default` keeps.
+/// Sorting by distance needs the `home` coordinates and does nothing without.
+pub fn sort_listings(
+    listings: Vec<ListingNode>,
+    keys: &[SortKey],
+    home: Option<&Coordinates>,
+) -> Vec<ListingNode> {
+    let mut indexed: Vec<(usize, ListingNode)> = listings.into_iter().enumerate().collect();
+    indexed.sort_by(|(index_a, a), (index_b, b)| {
+        keys.iter().fold(Ordering::Equal, |ordering, key| {
+            ordering.then_with(|| match key.category {
+                SortCategory::Default if key.descending => index_b.cmp(index_a),
+                SortCategory::Default => index_a.cmp(index_b),
+                _ => compare_listings(*key, a, b, home),
+            })
+        })
+    });
+    indexed.into_iter().map(|(_, listing)| listing).collect()
+}
+
+// Compare two listings by a sort key. Listings without a value for the
+// category always go last, whichever the direction.
+fn compare_listings(
+    key: SortKey,
+    a: &ListingNode,
+    b: &ListingNode,
+    home: Option<&Coordinates>,
+) -> Ordering {
+    fn missing_last<T: PartialOrd>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    let descending = key.descending;
+    match key.category {
+        SortCategory::Default => Ordering::Equal,
+        SortCategory::Title => missing_last(
+            Some(a.title.to_lowercase()),
+            Some(b.title.to_lowercase()),
+            descending,
+        ),
+        SortCategory::Price => missing_last(a.price(), b.price(), descending),
+        SortCategory::Seller => missing_last(
+            Some(a.sellerInfo.alias.to_lowercase()),
+            Some(b.sellerInfo.alias.to_lowercase()),
+            descending,
+        ),
+        // Newest first when ascending, so negate the timestamp
+        SortCategory::Date => missing_last(
+            a.posted_at().map(|t| -t.timestamp()),
+            b.posted_at().map(|t| -t.timestamp()),
+            descending,
+        ),
+        SortCategory::Location => missing_last(a.location(), b.location(), descending),
+        SortCategory::Distance => match home {
+            Some(home) => missing_last(a.distance_km(home), b.distance_km(home), descending),
+            None => Ordering::Equal,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(listings: &[ListingNode]) -> Vec<&str> {
+        listings.iter().map(|l| l.listingID.as_str()).collect()
+    }
+
+    #[test]
+    fn test_unpriced_listings_stay_last_in_both_directions() {
+        let listings = vec![
+            ListingNode::test("a"),
+            ListingNode::test("b").with_price("CHF 20.–"),
+            ListingNode::test("c").with_price("CHF 5.–"),
+        ];
+        let mut key = SortKey::parse("price").unwrap();
+        assert_eq!(
+            ids(&sort_listings(listings.clone(), &[key], None)),
+            ["c", "b", "a"]
+        );
+        key.toggle_direction();
+        assert_eq!(ids(&sort_listings(listings, &[key], None)), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_secondary_key_breaks_ties() {
+        let dated = |id: &str, seller: &str, price: &str, timestamp: &str| {
+            ListingNode::test(id)
+                .with_seller(seller)
+                .with_price(price)
+                .with_timestamp(timestamp)
+        };
+        let listings = vec![
+            dated("a", "Bob", "30", "2024-05-01T10:00:00+02:00"),
+            dated("b", "alice", "10", "2024-05-03T10:00:00+02:00"),
+            dated("c", "bob", "20", "2024-05-02T10:00:00+02:00"),
+        ];
+        let keys = [
+            SortKey::parse("seller").unwrap(),
+            SortKey::parse("price desc").unwrap(),
+        ];
+        assert_eq!(
+            ids(&sort_listings(listings.clone(), &keys, None)),
+            ["b", "a", "c"]
+        );
+
+        let newest_first = [SortKey::parse("date").unwrap()];
+        assert_eq!(
+            ids(&sort_listings(listings, &newest_first, None)),
+            ["b", "c", "a"]
+        );
+    }
+
+    #[test]
+    fn test_distance_needs_home() {
+        let listings = vec![
+            ListingNode::test("bern").with_postcode("3011"),
+            ListingNode::test("unknown"),
+            ListingNode::test("winterthur").with_postcode("8400"),
+        ];
+        let keys = [SortKey::parse("distance").unwrap()];
+        let zurich = tutti_frutti::geo::postcode_coordinates("8001").unwrap();
+        assert_eq!(
+            ids(&sort_listings(listings.clone(), &keys, Some(&zurich))),
+            ["winterthur", "bern", "unknown"]
+        );
+        assert_eq!(
+            ids(&sort_listings(listings, &keys, None)),
+            ["bern", "unknown", "winterthur"]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_direction() {
+        assert_eq!(SortKey::parse("title sideways"), None);
+        assert_eq!(SortKey::parse("colour"), None);
+        assert!(SortKey::parse("Date DESC").unwrap().descending);
+    }
+}
@@ -0,0 +1,348 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use crossterm::{cursor::MoveTo, queue};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, Rgba};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+};
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Write};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tutti_frutti::graphql::ListingNode;
+use tutti_frutti::images::{CachedImage, ImageCache};
+
+/// How pictures are drawn in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageProtocol {
+    /// Pick the best protocol the terminal advertises.
+    Auto,
+    /// Unicode half blocks in 24-bit color, works in any modern terminal.
+    Halfblocks,
+    Kitty,
+    Iterm2,
+    Sixel,
+    /// Text only.
+    Off,
+}
+
+impl ImageProtocol {
+    pub const NAMES: [&'static str; 6] = ["auto", "halfblocks", "kitty", "iterm2", "sixel", "off"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "auto" => Some(ImageProtocol::Auto),
+            "halfblocks" => Some(ImageProtocol::Halfblocks),
+            "kitty" => Some(ImageProtocol::Kitty),
+            "iterm2" => Some(ImageProtocol::Iterm2),
+            "sixel" => Some(ImageProtocol::Sixel),
+            "off" => Some(ImageProtocol::Off),
+            _ => None,
+        }
+    }
+
+    /// Resolves `Auto` from the environment variables terminals set.
+    pub fn resolve(self) -> Self {
+        if self != ImageProtocol::Auto {
+            return self;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+        if term == "xterm-kitty" || env::var_os("KITTY_WINDOW_ID").is_some() {
+            ImageProtocol::Kitty
+        } else if matches!(term_program.as_str(), "iTerm.app" | "WezTerm") {
+            ImageProtocol::Iterm2
+        } else if term.contains("sixel")
+            || matches!(term.as_str(), "foot" | "mlterm" | "yaft-256color")
+        {
+            ImageProtocol::Sixel
+        } else {
+            ImageProtocol::Halfblocks
+        }
+    }
+
+    /// Whether pictures are written to the terminal directly instead of
+    /// being drawn with text cells.
+    pub fn is_graphics(&self) -> bool {
+        matches!(
+            self,
+            ImageProtocol::Kitty | ImageProtocol::Iterm2 | ImageProtocol::Sixel
+        )
+    }
+}
+
+pub enum ThumbnailState {
+    Loading,
+    Ready(CachedImage, DynamicImage),
+    Failed(String),
+}
+
+/// Thumbnails of listings, downloaded in the background through the disk cache.
+pub struct Thumbnails {
+    // Why downloads are impossible if the cache could not be set up
+    cache: Result<ImageCache, String>,
+    states: HashMap<String, ThumbnailState>,
+    sender: UnboundedSender<(String, Result<CachedImage, String>)>,
+    receiver: UnboundedReceiver<(String, Result<CachedImage, String>)>,
+}
+
+impl Thumbnails {
+    pub fn new(cache: Result<ImageCache, String>) -> Self {
+        let (sender, receiver) = unbounded_channel();
+        Thumbnails {
+            cache,
+            states: HashMap::new(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Starts downloading the thumbnail of a listing unless it is known already.
+    pub fn request(&mut self, listing: &ListingNode) {
+        let Some(url) = listing.thumbnail_url() else {
+            return;
+        };
+        if self.states.contains_key(&listing.listingID) {
+            return;
+        }
+        let cache = match &self.cache {
+            Ok(cache) => cache.clone(),
+            Err(e) => {
+                self.states
+                    .insert(listing.listingID.clone(), ThumbnailState::Failed(e.clone()));
+                return;
+            }
+        };
+        self.states
+            .insert(listing.listingID.clone(), ThumbnailState::Loading);
+        let sender = self.sender.clone();
+        let listing_id = listing.listingID.clone();
+        let url = url.to_string();
+        tokio::spawn(async move {
+            let result = cache.fetch(&url).await.map_err(|e| e.to_string());
+            let _ = sender.send((listing_id, result));
+        });
+    }
+
+    /// Picks up finished downloads.
+    pub fn poll(&mut self) {
+        while let Ok((listing_id, result)) = self.receiver.try_recv() {
+            let state = match result.and_then(|image| {
+                let decoded = image.decode().map_err(|e| e.to_string())?;
+                Ok((image, decoded))
+            }) {
+                Ok((image, decoded)) => ThumbnailState::Ready(image, decoded),
+                Err(e) => ThumbnailState::Failed(e),
+            };
+            self.states.insert(listing_id, state);
+        }
+    }
+
+    pub fn get(&self, listing_id: &str) -> Option<&ThumbnailState> {
+        self.states.get(listing_id)
+    }
+}
+
+/// Largest size in cells that keeps the aspect ratio of the image, assuming
+/// cells twice as high as wide.
+pub fn fit(image: &DynamicImage, max_cols: u16, max_rows: u16) -> (u16, u16) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 || max_cols == 0 || max_rows == 0 {
+        return (0, 0);
+    }
+    let aspect = width as f64 / height as f64;
+    let cols = (max_rows as f64 * 2.0 * aspect).min(max_cols as f64);
+    let rows = (cols / aspect / 2.0).round().clamp(1.0, max_rows as f64);
+    (cols.round().max(1.0) as u16, rows as u16)
+}
+
+/// Draws an image with "▀": the foreground colors the upper half of a cell,
+/// the background the lower half.
+pub fn halfblocks(image: &DynamicImage, cols: u16, rows: u16) -> Vec<Line<'static>> {
+    let small = image
+        .resize_exact(cols as u32, rows as u32 * 2, FilterType::Triangle)
+        .to_rgb8();
+    (0..rows as u32)
+        .map(|row| {
+            let spans: Vec<Span> = (0..cols as u32)
+                .map(|col| {
+                    let top = small.get_pixel(col, row * 2);
+                    let bottom = small.get_pixel(col, row * 2 + 1);
+                    Span::styled(
+                        "▀",
+                        Style::default()
+                            .fg(Color::Rgb(top[0], top[1], top[2]))
+                            .bg(Color::Rgb(bottom[0], bottom[1], bottom[2])),
+                    )
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Writes an image over `area` with a terminal graphics protocol.
+pub fn draw_graphics(
+    out: &mut impl Write,
+    protocol: ImageProtocol,
+    image: &CachedImage,
+    decoded: &DynamicImage,
+    area: Rect,
+) -> io::Result<()> {
+    queue!(out, MoveTo(area.x, area.y))?;
+    match protocol {
+        ImageProtocol::Kitty => {
+            let rgba = decoded.to_rgba8();
+            let payload = BASE64.encode(rgba.as_raw());
+            let chunks: Vec<&[u8]> = payload.as_bytes().chunks(4096).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = u8::from(i + 1 < chunks.len());
+                if i == 0 {
+                    write!(
+                        out,
+                        "\x1b_Ga=T,f=32,s={},v={},c={},r={},C=1,q=2,m={};",
+                        rgba.width(),
+                        rgba.height(),
+                        area.width,
+                        area.height,
+                        more
+                    )?;
+                } else {
+                    write!(out, "\x1b_Gm={};", more)?;
+                }
+                out.write_all(chunk)?;
+                write!(out, "\x1b\\")?;
+            }
+        }
+        ImageProtocol::Iterm2 => {
+            write!(
+                out,
+                "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+                image.bytes.len(),
+                area.width,
+                area.height,
+                BASE64.encode(&image.bytes)
+            )?;
+        }
+        ImageProtocol::Sixel => {
+            let (cell_width, cell_height) = cell_size();
+            let resized = decoded.resize_exact(
+                area.width as u32 * cell_width,
+                area.height as u32 * cell_height,
+                FilterType::Triangle,
+            );
+            out.write_all(&sixel(&resized))?;
+        }
+        _ => {}
+    }
+    out.flush()
+}
+
+/// Removes pictures drawn with the kitty protocol, which unlike the others
+/// survive clearing the screen.
+pub fn clear_graphics(out: &mut impl Write, protocol: ImageProtocol) -> io::Result<()> {
+    if protocol == ImageProtocol::Kitty {
+        write!(out, "\x1b_Ga=d,q=2\x1b\\")?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+// Pixel size of a cell, guessing if the terminal does not report it
+fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns) as u32,
+            (size.height / size.rows) as u32,
+        ),
+        _ => (8, 16),
+    }
+}
+
+// Encodes an image as sixels with a fixed 6x6x6 color cube
+fn sixel(image: &DynamicImage) -> Vec<u8> {
+    fn level(value: u8) -> usize {
+        (value as usize * 5 + 127) / 255
+    }
+    fn color_index(pixel: &Rgba<u8>) -> usize {
+        level(pixel[0]) * 36 + level(pixel[1]) * 6 + level(pixel[2])
+    }
+
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let mut out = Vec::new();
+    let _ = write!(out, "\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for index in 0..216 {
+        let (r, g, b) = (index / 36, index / 6 % 6, index % 6);
+        let _ = write!(out, "#{};2;{};{};{}", index, r * 20, g * 20, b * 20);
+    }
+
+    for band in (0..height).step_by(6) {
+        // Sixel bits of every column, per color present in this band
+        let mut columns: HashMap<usize, Vec<u8>> = HashMap::new();
+        for y in band..(band + 6).min(height) {
+            for x in 0..width {
+                let pixel = rgba.get_pixel(x, y);
+                if pixel[3] < 128 {
+                    continue;
+                }
+                let bits = columns
+                    .entry(color_index(pixel))
+                    .or_insert_with(|| vec![0; width as usize]);
+                bits[x as usize] |= 1 << (y - band);
+            }
+        }
+        let mut colors: Vec<_> = columns.into_iter().collect();
+        colors.sort_by_key(|(index, _)| *index);
+        for (index, bits) in colors {
+            let _ = write!(out, "#{}", index);
+            let mut x = 0;
+            while x < bits.len() {
+                let run = bits[x..].iter().take_while(|&&b| b == bits[x]).count();
+                let ch = (63 + bits[x]) as char;
+                if run > 3 {
+                    let _ = write!(out, "!{}{}", run, ch);
+                } else {
+                    out.extend(std::iter::repeat_n(ch as u8, run));
+                }
+                x += run;
+            }
+            out.push(b'$');
+        }
+        out.push(b'-');
+    }
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn test_fit_keeps_aspect_ratio() {
+        let wide = DynamicImage::ImageRgb8(RgbImage::new(235, 167));
+        assert_eq!(fit(&wide, 40, 12), (34, 12));
+        assert_eq!(fit(&wide, 20, 12), (20, 7));
+    }
+
+    #[test]
+    fn test_halfblocks_use_two_pixels_per_cell() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(2, 4, |_, y| {
+            if y % 2 == 0 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        }));
+        let lines = halfblocks(&image, 2, 2);
+        assert_eq!(lines.len(), 2);
+        let style = lines[0].spans[0].style;
+        assert_eq!(style.fg, Some(Color::Rgb(255, 0, 0)));
+        assert_eq!(style.bg, Some(Color::Rgb(0, 0, 255)));
+    }
+}
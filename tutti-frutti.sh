@@ -49,6 +49,14 @@ query SearchListingsByConstraints($query: String, $constraints: ListingSearchCon
               src
             }
           }
+          postcodeInformation {
+            postcode
+            locationName
+            canton {
+              shortName
+              name
+            }
+          }
         }
       }
     }
@@ -0,0 +1,367 @@
+use crate::graphql::ListingNode;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// How prices are grouped into histogram bins.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Binning {
+    /// Bins of equal width.
+    Linear,
+    /// Bins of equal width on a logarithmic scale, so a few very expensive
+    /// listings do not squash everything else into the first bin.
+    Log,
+    /// Bin width from the Freedman–Diaconis rule, based on the IQR.
+    Adaptive,
+}
+
+impl fmt::Display for Binning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Binning::Linear => "linear",
+            Binning::Log => "log",
+            Binning::Adaptive => "adaptive",
+        })
+    }
+}
+
+/// Options for computing price statistics.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatsOptions {
+    pub binning: Binning,
+    /// Number of bins for linear and log binning, the upper limit for adaptive.
+    pub bins: usize,
+    /// Leave out prices more than 1.5 IQR outside the quartiles.
+    pub exclude_outliers: bool,
+    /// Fraction cut from each end for the trimmed mean, e.g. 0.1 for 10%.
+    pub trim: f64,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        StatsOptions {
+            binning: Binning::Linear,
+            bins: 10,
+            exclude_outliers: false,
+            trim: 0.1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistogramBin {
+    pub start: f64,
+    pub end: f64,
+    pub count: usize,
+}
+
+/// Summary of the prices of a set of listings. Listings without a parseable
+/// price are ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PriceStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub trimmed_mean: f64,
+    pub std_dev: f64,
+    pub median: f64,
+    pub p10: f64,
+    pub q1: f64,
+    pub q3: f64,
+    pub p90: f64,
+    /// Prices left out because of `StatsOptions::exclude_outliers`.
+    pub outliers: usize,
+    pub histogram: Vec<HistogramBin>,
+}
+
+impl PriceStats {
+    pub fn from_listings(listings: &[ListingNode], options: &StatsOptions) -> Self {
+        let prices: Vec<f64> = listings.iter().filter_map(|l| l.price()).collect();
+        PriceStats::from_prices(&prices, options)
+    }
+
+    pub fn from_prices(prices: &[f64], options: &StatsOptions) -> Self {
+        let mut prices: Vec<f64> = prices.iter().copied().filter(|p| p.is_finite()).collect();
+        prices.sort_by(|a, b| a.total_cmp(b));
+
+        let mut outliers = 0;
+        if options.exclude_outliers && prices.len() >= 4 {
+            let (low, high) = iqr_fences(&prices);
+            let total = prices.len();
+            prices.retain(|&p| p >= low && p <= high);
+            outliers = total - prices.len();
+        }
+
+        if prices.is_empty() {
+            return PriceStats {
+                outliers,
+                ..PriceStats::default()
+            };
+        }
+
+        let count = prices.len();
+        let mean = prices.iter().sum::<f64>() / count as f64;
+        let std_dev = if count > 1 {
+            let variance =
+                prices.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / (count - 1) as f64;
+            variance.sqrt()
+        } else {
+            0.0
+        };
+
+        let trim = (count as f64 * options.trim.clamp(0.0, 0.49)).floor() as usize;
+        let trimmed = &prices[trim..count - trim];
+        let trimmed_mean = trimmed.iter().sum::<f64>() / trimmed.len() as f64;
+
+        PriceStats {
+            count,
+            min: prices[0],
+            max: prices[count - 1],
+            mean,
+            trimmed_mean,
+            std_dev,
+            median: percentile(&prices, 0.5),
+            p10: percentile(&prices, 0.1),
+            q1: percentile(&prices, 0.25),
+            q3: percentile(&prices, 0.75),
+            p90: percentile(&prices, 0.9),
+            outliers,
+            histogram: histogram(&prices, options),
+        }
+    }
+}
+
+/// Percentile `p` (0.0 to 1.0) of sorted, non-empty values, interpolating
+/// linearly between neighbours.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+// Tukey's fences: anything outside is considered an outlier
+fn iqr_fences(sorted: &[f64]) -> (f64, f64) {
+    let q1 = percentile(sorted, 0.25);
+    let q3 = percentile(sorted, 0.75);
+    let iqr = q3 - q1;
+    (q1 - 1.5 * iqr, q3 + 1.5 * iqr)
+}
+
+fn histogram(sorted: &[f64], options: &StatsOptions) -> Vec<HistogramBin> {
+    let min = sorted[0];
+    let max = sorted[sorted.len() - 1];
+    let bins = options.bins.max(1);
+
+    // If all prices are the same
+    if max <= min {
+        return vec![HistogramBin {
+            start: min,
+            end: max,
+            count: sorted.len(),
+        }];
+    }
+
+    let edges: Vec<f64> = match options.binning {
+        Binning::Linear => linear_edges(min, max, bins),
+        Binning::Log => {
+            // Free items would be at minus infinity, so start at CHF 1
+            let (low, high) = (min.max(1.0).ln(), max.max(1.0).ln());
+            if high <= low {
+                linear_edges(min, max, bins)
+            } else {
+                let mut edges: Vec<f64> = linear_edges(low, high, bins)
+                    .into_iter()
+                    .map(f64::exp)
+                    .collect();
+                edges[0] = min;
+                edges
+            }
+        }
+        Binning::Adaptive => {
+            let n = sorted.len() as f64;
+            let iqr = percentile(sorted, 0.75) - percentile(sorted, 0.25);
+            let adaptive = if iqr > 0.0 {
+                ((max - min) / (2.0 * iqr / n.cbrt())).ceil() as usize
+            } else {
+                // Sturges' rule when the IQR is degenerate
+                n.log2().ceil() as usize + 1
+            };
+            linear_edges(min, max, adaptive.clamp(1, bins))
+        }
+    };
+
+    let mut histogram: Vec<HistogramBin> = edges
+        .windows(2)
+        .map(|w| HistogramBin {
+            start: w[0],
+            end: w[1],
+            count: 0,
+        })
+        .collect();
+    for &price in sorted {
+        // The last bin also includes the maximum
+        let index = histogram
+            .iter()
+            .position(|bin| price < bin.end)
+            .unwrap_or(histogram.len() - 1);
+        histogram[index].count += 1;
+    }
+    histogram
+}
+
+fn linear_edges(min: f64, max: f64, bins: usize) -> Vec<f64> {
+    let width = (max - min) / bins as f64;
+    let mut edges: Vec<f64> = (0..bins).map(|i| min + i as f64 * width).collect();
+    edges.push(max);
+    edges
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SellerStats {
+    pub alias: String,
+    pub count: usize,
+    /// Average over the seller's listings that have a price.
+    pub mean_price: Option<f64>,
+}
+
+/// Listing count and average price per seller, most active sellers first.
+pub fn seller_stats(listings: &[ListingNode]) -> Vec<SellerStats> {
+    let mut by_seller: HashMap<&str, (usize, Vec<f64>)> = HashMap::new();
+    for listing in listings {
+        let entry = by_seller.entry(&listing.sellerInfo.alias).or_default();
+        entry.0 += 1;
+        entry.1.extend(listing.price());
+    }
+
+    let mut stats: Vec<SellerStats> = by_seller
+        .into_iter()
+        .map(|(alias, (count, prices))| SellerStats {
+            alias: alias.to_string(),
+            count,
+            mean_price: (!prices.is_empty())
+                .then(|| prices.iter().sum::<f64>() / prices.len() as f64),
+        })
+        .collect();
+    stats.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.alias.cmp(&b.alias)));
+    stats
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DailyCount {
+    pub date: NaiveDate,
+    pub count: usize,
+}
+
+/// Number of listings posted per day, including days without any.
+pub fn listings_per_day(listings: &[ListingNode]) -> Vec<DailyCount> {
+    let mut counts: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for posted_at in listings.iter().filter_map(|l| l.posted_at()) {
+        *counts.entry(posted_at.date_naive()).or_default() += 1;
+    }
+
+    let (Some(&first), Some(&last)) = (counts.keys().next(), counts.keys().next_back()) else {
+        return Vec::new();
+    };
+    first
+        .iter_days()
+        .take_while(|day| *day <= last)
+        .map(|date| DailyCount {
+            date,
+            count: counts.get(&date).copied().unwrap_or(0),
+        })
+        .collect()
+}
+
+/// Everything this module computes for a set of listings, e.g. for a JSON report.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Summary {
+    pub listings: usize,
+    pub prices: PriceStats,
+    pub sellers: Vec<SellerStats>,
+    pub per_day: Vec<DailyCount>,
+}
+
+pub fn summarize(listings: &[ListingNode], options: &StatsOptions) -> Summary {
+    Summary {
+        listings: listings.len(),
+        prices: PriceStats::from_listings(listings, options),
+        sellers: seller_stats(listings),
+        per_day: listings_per_day(listings),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles_and_spread() {
+        let prices: Vec<f64> = (1..=11).map(|p| p as f64 * 10.0).collect();
+        let stats = PriceStats::from_prices(&prices, &StatsOptions::default());
+
+        assert_eq!(stats.count, 11);
+        assert_eq!(stats.median, 60.0);
+        assert_eq!(stats.q1, 35.0);
+        assert_eq!(stats.q3, 85.0);
+        assert_eq!(stats.p10, 20.0);
+        assert_eq!(stats.p90, 100.0);
+        assert_eq!(stats.trimmed_mean, 60.0);
+        assert!((stats.std_dev - 33.166).abs() < 0.001);
+        assert_eq!(stats.histogram.iter().map(|b| b.count).sum::<usize>(), 11);
+    }
+
+    #[test]
+    fn test_outlier_exclusion() {
+        let prices = [100.0, 120.0, 110.0, 130.0, 90.0, 99_999.0];
+        let options = StatsOptions {
+            exclude_outliers: true,
+            ..StatsOptions::default()
+        };
+        let stats = PriceStats::from_prices(&prices, &options);
+
+        assert_eq!(stats.outliers, 1);
+        assert_eq!(stats.count, 5);
+        assert_eq!(stats.max, 130.0);
+    }
+
+    #[test]
+    fn test_log_binning_spreads_skewed_prices() {
+        let prices = [10.0, 12.0, 15.0, 20.0, 25.0, 10_000.0];
+        let options = StatsOptions {
+            binning: Binning::Log,
+            bins: 3,
+            ..StatsOptions::default()
+        };
+        let stats = PriceStats::from_prices(&prices, &options);
+        let counts: Vec<usize> = stats.histogram.iter().map(|b| b.count).collect();
+
+        assert_eq!(counts, [5, 0, 1]);
+    }
+
+    #[test]
+    fn test_summary_serializes_to_json() {
+        let listing = |seller: &str, timestamp: &str| {
+            ListingNode::test("1")
+                .with_seller(seller)
+                .with_timestamp(timestamp)
+        };
+        let listings = [
+            listing("anna", "2024-05-01T10:00:00+02:00").with_price("CHF 50.–"),
+            listing("anna", "2024-05-03T10:00:00+02:00"),
+            listing("ben", "2024-05-03T12:00:00+02:00").with_price("CHF 150.–"),
+        ];
+        let summary = summarize(&listings, &StatsOptions::default());
+        let json = serde_json::to_value(&summary).unwrap();
+
+        assert_eq!(json["prices"]["count"], 2);
+        assert_eq!(json["prices"]["median"], 100.0);
+        assert_eq!(json["sellers"][0]["alias"], "anna");
+        assert_eq!(json["sellers"][0]["mean_price"], 50.0);
+        assert_eq!(json["per_day"][1]["date"], "2024-05-02");
+        assert_eq!(json["per_day"][1]["count"], 0);
+    }
+}
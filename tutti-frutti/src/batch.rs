@@ -0,0 +1,207 @@
+use crate::client::Session;
+use crate::errors::FetchListingsError;
+use crate::graphql::ListingNode;
+use crate::SearchConfig;
+use std::collections::HashMap;
+use std::fmt;
+
+/// What to search for.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchRequest {
+    /// Text to search for. Empty to browse a whole category.
+    pub query: String,
+    /// Only search this category, see `categories::fetch_categories`.
+    pub category: Option<String>,
+    /// Overrides `SearchConfig::max_pages` for this query.
+    pub max_pages: Option<usize>,
+}
+
+impl SearchRequest {
+    pub fn new(query: impl Into<String>) -> Self {
+        SearchRequest {
+            query: query.into(),
+            ..SearchRequest::default()
+        }
+    }
+
+    /// All listings of a category, without a text query.
+    pub fn browse(category_id: impl Into<String>) -> Self {
+        SearchRequest {
+            category: Some(category_id.into()),
+            ..SearchRequest::default()
+        }
+    }
+
+    pub fn in_category(mut self, category_id: impl Into<String>) -> Self {
+        self.category = Some(category_id.into());
+        self
+    }
+}
+
+/// The query and category, e.g. "flyer [bikes]", or "[bikes]" when browsing
+/// a category.
+impl fmt::Display for SearchRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.category {
+            Some(category) if self.query.is_empty() => write!(f, "[{}]", category),
+            Some(category) => write!(f, "{} [{}]", self.query, category),
+            None => f.write_str(&self.query),
+        }
+    }
+}
+
+impl From<&str> for SearchRequest {
+    fn from(query: &str) -> Self {
+        SearchRequest::new(query)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Most requests in flight at once, across all queries.
+    pub concurrency: usize,
+    /// Also merge all results into one set without duplicates.
+    pub merge: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            concurrency: 8,
+            merge: false,
+        }
+    }
+}
+
+/// Result of one query of a batch.
+#[derive(Debug)]
+pub struct QueryOutcome {
+    pub request: SearchRequest,
+    pub result: Result<Vec<ListingNode>, FetchListingsError>,
+}
+
+#[derive(Debug)]
+pub struct BatchResults {
+    /// One outcome per request, in the order of the requests.
+    pub outcomes: Vec<QueryOutcome>,
+    /// The listings of all successful queries, if `BatchOptions::merge` is set.
+    pub merged: Option<MergedListings>,
+}
+
+impl BatchResults {
+    /// Requests that failed, with their errors.
+    pub fn errors(&self) -> impl Iterator<Item = (&SearchRequest, &FetchListingsError)> {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| match &outcome.result {
+                Ok(_) => None,
+                Err(e) => Some((&outcome.request, e)),
+            })
+    }
+}
+
+/// Results of several queries, merged by listing ID.
+#[derive(Debug, Clone, Default)]
+pub struct MergedListings {
+    /// Each listing once, in the order it was first found.
+    pub listings: Vec<ListingNode>,
+    /// Queries whose results contained each listing, by listing ID, in the
+    /// order of the queries. A batch names each request by its `Display`,
+    /// so the same query in two categories is told apart.
+    pub matched: HashMap<String, Vec<String>>,
+}
+
+impl MergedListings {
+    pub fn merge(results: Vec<(String, Vec<ListingNode>)>) -> Self {
+        let mut merged = MergedListings::default();
+        for (query, listings) in results {
+            for listing in listings {
+                let matched = merged.matched.entry(listing.listingID.clone()).or_default();
+                if matched.is_empty() {
+                    merged.listings.push(listing);
+                }
+                if !matched.contains(&query) {
+                    matched.push(query.clone());
+                }
+            }
+        }
+        merged
+    }
+
+    /// Queries that found a listing.
+    pub fn matched_by(&self, listing_id: &str) -> &[String] {
+        self.matched.get(listing_id).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Runs many queries over one session, with at most
+/// `options.concurrency` requests in flight. Only failing to start the
+/// session is an error; failed queries are reported in their outcome.
+pub async fn fetch_batch(
+    requests: &[SearchRequest],
+    config: &SearchConfig,
+    options: &BatchOptions,
+) -> Result<BatchResults, FetchListingsError> {
+    let session = Session::start(config)
+        .await?
+        .with_concurrency(options.concurrency);
+
+    let results =
+        futures::future::join_all(requests.iter().map(|request| session.fetch(request))).await;
+    let outcomes: Vec<QueryOutcome> = requests
+        .iter()
+        .cloned()
+        .zip(results)
+        .map(|(request, result)| QueryOutcome { request, result })
+        .collect();
+
+    let merged = options.merge.then(|| {
+        MergedListings::merge(
+            outcomes
+                .iter()
+                .filter_map(|outcome| {
+                    let listings = outcome.result.as_ref().ok()?;
+                    Some((outcome.request.to_string(), listings.clone()))
+                })
+                .collect(),
+        )
+    });
+    Ok(BatchResults { outcomes, merged })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_records_matching_queries() {
+        let listing = ListingNode::test;
+        let merged = MergedListings::merge(vec![
+            ("velo".to_string(), vec![listing("1"), listing("2")]),
+            ("vélo".to_string(), vec![listing("2"), listing("3")]),
+        ]);
+        let ids: Vec<&str> = merged
+            .listings
+            .iter()
+            .map(|l| l.listingID.as_str())
+            .collect();
+        assert_eq!(ids, ["1", "2", "3"]);
+        assert_eq!(merged.matched_by("2"), ["velo", "vélo"]);
+        assert_eq!(merged.matched_by("3"), ["vélo"]);
+        assert!(merged.matched_by("4").is_empty());
+    }
+
+    #[test]
+    fn test_requests_are_named_by_query_and_category() {
+        assert_eq!(SearchRequest::new("velo").to_string(), "velo");
+        assert_eq!(SearchRequest::browse("bikes").to_string(), "[bikes]");
+        assert_eq!(
+            SearchRequest::new("flyer").in_category("bikes").to_string(),
+            "flyer [bikes]"
+        );
+        assert_ne!(
+            SearchRequest::browse("bikes").to_string(),
+            SearchRequest::browse("cars").to_string()
+        );
+    }
+}
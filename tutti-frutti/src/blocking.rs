@@ -0,0 +1,242 @@
+use crate::batch::SearchRequest;
+use crate::categories::CategoryTree;
+use crate::client;
+use crate::errors::FetchListingsError;
+use crate::graphql::{ListingNode, ParseWarning};
+use crate::seller::{SellerProfile, SellerRef};
+use crate::SearchConfig;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::{Builder, Runtime};
+
+/// Like `crate::fetch_listings`, blocking until all pages are fetched.
+pub fn fetch_listings(search_query: &str) -> Result<Vec<ListingNode>, FetchListingsError> {
+    fetch_listings_with_config(search_query, &SearchConfig::default())
+}
+
+/// Like `crate::fetch_listings_with_config`.
+pub fn fetch_listings_with_config(
+    search_query: &str,
+    config: &SearchConfig,
+) -> Result<Vec<ListingNode>, FetchListingsError> {
+    Session::start(config)?.search(search_query)
+}
+
+/// A `client::Session` whose methods block, for programs without an async
+/// runtime. It runs its own single-threaded tokio runtime, shared by its
+/// clones, so it must not be used from within an async runtime. Stale cache
+/// entries are refreshed before answering, as nothing runs in the background
+/// between calls.
+#[derive(Debug, Clone)]
+pub struct Session {
+    inner: client::Session,
+    runtime: Arc<Runtime>,
+}
+
+impl Session {
+    /// Like `client::Session::start`.
+    pub fn start(config: &SearchConfig) -> Result<Self, FetchListingsError> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let inner = runtime
+            .block_on(client::Session::start(config))?
+            .with_inline_revalidation();
+        Ok(Session {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Limits the number of requests in flight, see
+    /// `client::Session::with_concurrency`.
+    pub fn with_concurrency(mut self, max_requests: usize) -> Self {
+        self.inner = self.inner.with_concurrency(max_requests);
+        self
+    }
+
+    pub fn config(&self) -> &SearchConfig {
+        self.inner.config()
+    }
+
+    pub fn take_warnings(&self) -> Vec<ParseWarning> {
+        self.inner.take_warnings()
+    }
+
+    /// Fetches all pages of a query, up to `max_pages` of the config.
+    pub fn search(&self, search_query: &str) -> Result<Vec<ListingNode>, FetchListingsError> {
+        self.block_on(self.inner.search(search_query))
+    }
+
+    /// Fetches the listings of a request, up to its `max_pages` or else
+    /// those of the config.
+    pub fn fetch(&self, request: &SearchRequest) -> Result<Vec<ListingNode>, FetchListingsError> {
+        self.block_on(self.inner.fetch(request))
+    }
+
+    /// Fetches one listing by its ID, see `client::Session::listing`.
+    pub fn listing(&self, listing_id: &str) -> Result<ListingNode, FetchListingsError> {
+        self.block_on(self.inner.listing(listing_id))
+    }
+
+    /// Runs any GraphQL document, see `client::Session::execute`.
+    pub fn execute<T: DeserializeOwned>(
+        &self,
+        document: &str,
+        variables: serde_json::Value,
+    ) -> Result<T, FetchListingsError> {
+        self.block_on(self.inner.execute(document, variables))
+    }
+
+    /// The profile and listings of a seller, newest first.
+    pub fn seller_listings(
+        &self,
+        seller: impl Into<SellerRef>,
+    ) -> Result<(SellerProfile, Vec<ListingNode>), FetchListingsError> {
+        self.block_on(self.inner.seller_listings(&seller.into()))
+    }
+
+    pub fn categories(&self) -> Result<CategoryTree, FetchListingsError> {
+        self.block_on(self.inner.categories())
+    }
+
+    /// The async session, e.g. for batches.
+    pub fn as_async(&self) -> &client::Session {
+        &self.inner
+    }
+
+    /// Runs any future on the runtime of this session, e.g. one of
+    /// `as_async`.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::ResponseCache;
+    use crate::cassette::Cassette;
+    use crate::client::search_variables;
+    use crate::graphql::{GRAPHQL_QUERY, LISTING_QUERY};
+    use crate::locale::Locale;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn test_blocking_session_replays_a_cassette() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("cassette.json");
+        let search = json!({
+            "query": GRAPHQL_QUERY,
+            "variables": search_variables(Some("velo"), None, 0)
+        });
+        let listings = json!({ "data": { "searchListingsByQuery": { "listings": {
+            "totalCount": 1,
+            "edges": [{ "node": { "listingID": "42", "title": "Velo" } }]
+        }}}});
+        let detail = |id: &str, listing: serde_json::Value| {
+            json!({
+                "request": {
+                    "method": "POST",
+                    "url": "https://www.tutti.ch/api/v10/graphql",
+                    "headers": [],
+                    "body": json!({ "query": LISTING_QUERY, "variables": { "listingID": id } })
+                        .to_string()
+                },
+                "response": {
+                    "status": 200,
+                    "headers": [],
+                    "body": json!({ "data": { "listing": listing } }).to_string()
+                }
+            })
+        };
+        let interactions = json!({ "interactions": [
+            detail("42", json!({ "listingID": "42", "title": "Velo" })),
+            detail("43", serde_json::Value::Null),
+            {
+                "request": { "method": "GET", "url": "https://www.tutti.ch/", "headers": [] },
+                "response": { "status": 200, "headers": [], "body": "<html></html>" }
+            },
+            {
+                "request": {
+                    "method": "POST",
+                    "url": "https://www.tutti.ch/api/v10/graphql",
+                    "headers": [],
+                    "body": search.to_string()
+                },
+                "response": { "status": 200, "headers": [], "body": listings.to_string() }
+            }
+        ]});
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, interactions.to_string()).unwrap();
+
+        let config = SearchConfig {
+            cassette: Some(Cassette::replay(&path).unwrap()),
+            ..SearchConfig::default()
+        };
+        let session = Session::start(&config).unwrap();
+        let found = session.fetch(&SearchRequest::new("velo")).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].listingID, "42");
+        assert!(session.search("auto").is_err());
+        assert_eq!(session.listing("42").unwrap().title, "Velo");
+        assert!(matches!(
+            session.listing("43"),
+            Err(FetchListingsError::NotFound(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_blocking_session_refreshes_stale_entries() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("cassette.json");
+        let variables = search_variables(Some("velo"), None, 0);
+        let listings = |title: &str| {
+            json!({ "data": { "searchListingsByQuery": { "listings": {
+                "totalCount": 1,
+                "edges": [{ "node": { "listingID": "42", "title": title } }]
+            }}}})
+            .to_string()
+        };
+        let interactions = json!({ "interactions": [
+            {
+                "request": { "method": "GET", "url": "https://www.tutti.ch/", "headers": [] },
+                "response": { "status": 200, "headers": [], "body": "<html></html>" }
+            },
+            {
+                "request": {
+                    "method": "POST",
+                    "url": "https://www.tutti.ch/api/v10/graphql",
+                    "headers": [],
+                    "body": json!({ "query": GRAPHQL_QUERY, "variables": variables }).to_string()
+                },
+                "response": { "status": 200, "headers": [], "body": listings("Velo") }
+            }
+        ]});
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, interactions.to_string()).unwrap();
+
+        // Every entry is stale as soon as it is stored
+        let cache = ResponseCache {
+            ttl: Duration::ZERO,
+            ..ResponseCache::new(dir.join("cache"))
+        };
+        let key = ResponseCache::key(GRAPHQL_QUERY, &variables, Locale::De.accept_language());
+        std::fs::create_dir_all(&cache.dir).unwrap();
+        cache.put(&key, &listings("Old velo")).unwrap();
+
+        let config = SearchConfig {
+            cassette: Some(Cassette::replay(&path).unwrap()),
+            cache: Some(cache.clone()),
+            ..SearchConfig::default()
+        };
+        let session = Session::start(&config).unwrap();
+        assert_eq!(session.search("velo").unwrap()[0].title, "Velo");
+        drop(session);
+        assert_eq!(cache.get(&key).unwrap().unwrap().body, listings("Velo"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
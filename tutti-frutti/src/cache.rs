@@ -0,0 +1,170 @@
+use crate::util::{hex, remove_entry, write_atomic};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Caches GraphQL responses on disk, set as `SearchConfig::cache`.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    pub dir: PathBuf,
+    /// How long a response is served without asking tutti.ch.
+    pub ttl: Duration,
+    /// How long after the TTL a response is still served, while it is
+    /// refreshed in the background for the next time. A
+    /// `blocking::Session` refreshes it before answering instead.
+    pub stale_while_revalidate: Duration,
+    /// The oldest responses are removed once the cache is larger.
+    pub max_bytes: u64,
+    /// Only serve from the cache, whatever the age of the responses, and
+    /// fail requests that are not cached.
+    pub offline: bool,
+}
+
+impl ResponseCache {
+    /// A cache with a TTL of 5 minutes, served stale for up to an hour and
+    /// limited to 50 MB.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ResponseCache {
+            dir: dir.into(),
+            ttl: Duration::from_secs(5 * 60),
+            stale_while_revalidate: Duration::from_secs(3600),
+            max_bytes: 50 * 1024 * 1024,
+            offline: false,
+        }
+    }
+
+    /// Identifies a request by its document, variables and locale. The
+    /// headers that change with every request are left out.
+    pub fn key(document: &str, variables: &Value, locale: &str) -> String {
+        let mut hasher = Sha256::new();
+        for part in [document, &variables.to_string(), locale] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hex(&hasher.finalize())
+    }
+
+    pub fn get(&self, key: &str) -> io::Result<Option<CachedResponse>> {
+        match fs::read_to_string(self.path(key)) {
+            // An unreadable entry is as good as none
+            Ok(contents) => Ok(serde_json::from_str(&contents).ok()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stores a response body, then removes the oldest responses beyond
+    /// `max_bytes`.
+    pub fn put(&self, key: &str, body: &str) -> io::Result<()> {
+        let response = CachedResponse {
+            stored_at: Utc::now(),
+            body: body.to_string(),
+        };
+        write_atomic(
+            &self.path(key),
+            serde_json::to_string(&response)?.as_bytes(),
+        )?;
+        self.evict()
+    }
+
+    pub fn freshness(&self, response: &CachedResponse) -> Freshness {
+        let age = (Utc::now() - response.stored_at)
+            .to_std()
+            .unwrap_or_default();
+        if age < self.ttl {
+            Freshness::Fresh
+        } else if age < self.ttl + self.stale_while_revalidate {
+            Freshness::Stale
+        } else {
+            Freshness::Expired
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            // Leaves alone files being written
+            let is_entry = entry.path().extension().is_some_and(|ext| ext == "json");
+            if metadata.is_file() && is_entry {
+                entries.push((metadata.modified()?, metadata.len(), entry.path()));
+            }
+        }
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            remove_entry(&path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+/// A response body with the time it was stored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedResponse {
+    pub stored_at: DateTime<Utc>,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// Younger than the TTL.
+    Fresh,
+    /// Past the TTL but within `stale_while_revalidate`.
+    Stale,
+    Expired,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_entries_expire_and_are_evicted() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", uuid::Uuid::new_v4()));
+        let mut cache = ResponseCache::new(&dir);
+        let page = |offset: u32| ResponseCache::key("query", &json!({ "offset": offset }), "de");
+        assert_ne!(page(0), page(30));
+        assert_ne!(
+            page(0),
+            ResponseCache::key("query", &json!({ "offset": 0 }), "fr")
+        );
+
+        cache.put(&page(0), "first").unwrap();
+        let cached = cache.get(&page(0)).unwrap().unwrap();
+        assert_eq!(cached.body, "first");
+        assert_eq!(cache.freshness(&cached), Freshness::Fresh);
+        assert!(cache.get(&page(30)).unwrap().is_none());
+
+        let old = |minutes| CachedResponse {
+            stored_at: Utc::now() - chrono::Duration::minutes(minutes),
+            body: String::new(),
+        };
+        assert_eq!(cache.freshness(&old(10)), Freshness::Stale);
+        assert_eq!(cache.freshness(&old(70)), Freshness::Expired);
+
+        // Room for one entry only, the older one goes
+        cache.max_bytes = fs::metadata(cache.path(&page(0))).unwrap().len() + 10;
+        std::thread::sleep(Duration::from_millis(20));
+        cache.put(&page(30), "second").unwrap();
+        assert!(cache.get(&page(0)).unwrap().is_none());
+        assert_eq!(cache.get(&page(30)).unwrap().unwrap().body, "second");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,341 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Stands in for cookie values and tokens in recorded exchanges.
+pub const REDACTED: &str = "redacted";
+
+// Headers whose values are secrets, replaced by `REDACTED`
+const SECRET_HEADERS: &[&str] = &["x-csrf-token", "authorization"];
+// Headers listing cookies, whose values are replaced but not their names
+const COOKIE_HEADERS: &[&str] = &["cookie", "set-cookie"];
+
+/// One request and the response it got.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+impl RecordedRequest {
+    // Same method, URL and body; JSON bodies are compared as values
+    fn matches(&self, other: &RecordedRequest) -> bool {
+        let json = |body: &Option<String>| {
+            body.as_deref()
+                .and_then(|body| serde_json::from_str::<serde_json::Value>(body).ok())
+        };
+        self.method == other.method
+            && self.url == other.url
+            && match (json(&self.body), json(&other.body)) {
+                (Some(a), Some(b)) => a == b,
+                _ => self.body == other.body,
+            }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedResponse {
+    pub fn header_map(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        headers
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Requests are sent and every exchange is saved.
+    Record,
+    /// Requests are answered from the cassette without touching the network.
+    Replay,
+}
+
+#[derive(Debug)]
+struct State {
+    interactions: Vec<Interaction>,
+    // Whether each interaction was replayed already
+    served: Vec<bool>,
+    // Where to save the recording, if it changed since it was last saved
+    unsaved: Option<PathBuf>,
+}
+
+impl State {
+    fn save(&mut self) -> io::Result<()> {
+        let Some(path) = &self.unsaved else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = CassetteFile {
+            interactions: self.interactions.clone(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&file)?)?;
+        self.unsaved = None;
+        Ok(())
+    }
+}
+
+impl Drop for State {
+    // The last clone of a recording saves it, if `Cassette::save` did not
+    fn drop(&mut self) {
+        let _ = self.save();
+    }
+}
+
+/// A file of recorded HTTP exchanges with tutti.ch, set as
+/// `SearchConfig::cassette`. Clones share the same recording, so sessions
+/// started from one config all add to or replay from it.
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    mode: CassetteMode,
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+impl Cassette {
+    /// Records to `path`, replacing any earlier recording there. The file is
+    /// written by `save`, or else once the last clone is dropped.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Cassette {
+            mode: CassetteMode::Record,
+            state: Arc::new(Mutex::new(State {
+                interactions: Vec::new(),
+                served: Vec::new(),
+                unsaved: Some(path.clone()),
+            })),
+            path,
+        }
+    }
+
+    /// Replays the exchanges recorded at `path`.
+    pub fn replay(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let file: CassetteFile = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        Ok(Cassette {
+            mode: CassetteMode::Replay,
+            path,
+            state: Arc::new(Mutex::new(State {
+                served: vec![false; file.interactions.len()],
+                interactions: file.interactions,
+                unsaved: None,
+            })),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.lock().unwrap().interactions.clone()
+    }
+
+    /// The response to a request, from the first matching exchange that was
+    /// not replayed yet, or else the last matching one.
+    pub fn find(&self, request: &RecordedRequest) -> Option<RecordedResponse> {
+        let mut state = self.state.lock().unwrap();
+        let matching: Vec<usize> = (0..state.interactions.len())
+            .filter(|&i| state.interactions[i].request.matches(request))
+            .collect();
+        let index = matching
+            .iter()
+            .copied()
+            .find(|&i| !state.served[i])
+            .or(matching.last().copied())?;
+        state.served[index] = true;
+        Some(state.interactions[index].response.clone())
+    }
+
+    /// Adds an exchange, saved with the others by `save`.
+    pub fn push(&self, interaction: Interaction) {
+        let mut state = self.state.lock().unwrap();
+        state.interactions.push(interaction);
+        state.served.push(false);
+        state.unsaved = Some(self.path.clone());
+    }
+
+    /// Writes the exchanges recorded since the last save, if any, to `path`.
+    pub fn save(&self) -> io::Result<()> {
+        self.state.lock().unwrap().save()
+    }
+}
+
+/// The headers as name and value pairs, with cookie values and tokens
+/// replaced by `REDACTED`.
+pub fn redact_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = name.as_str().to_string();
+            let value = value.to_str().unwrap_or_default();
+            let value = if SECRET_HEADERS.contains(&name.as_str()) {
+                REDACTED.to_string()
+            } else if name == "set-cookie" {
+                // Keep the attributes after the first ';'
+                match value.split_once(';') {
+                    Some((cookie, attributes)) => {
+                        format!("{};{}", redact_cookie(cookie), attributes)
+                    }
+                    None => redact_cookie(value),
+                }
+            } else if COOKIE_HEADERS.contains(&name.as_str()) {
+                value
+                    .split(';')
+                    .map(redact_cookie)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            } else {
+                value.to_string()
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+/// The body with the values of the cookies set in `headers` replaced by
+/// `REDACTED`, e.g. the CSRF token that the home page also embeds. Values
+/// shorter than 8 characters are left alone, as they are no secrets and
+/// could appear anywhere.
+pub fn redact_body(body: &str, headers: &HeaderMap) -> String {
+    let mut body = body.to_string();
+    for value in headers.get_all("set-cookie") {
+        let cookie = value.to_str().unwrap_or_default();
+        let cookie = cookie.split(';').next().unwrap_or_default();
+        if let Some((_, secret)) = cookie.trim().split_once('=') {
+            if secret.len() >= 8 && secret != REDACTED {
+                body = body.replace(secret, REDACTED);
+            }
+        }
+    }
+    body
+}
+
+fn redact_cookie(cookie: &str) -> String {
+    match cookie.trim().split_once('=') {
+        Some((name, _)) => format!("{}={}", name, REDACTED),
+        None => cookie.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_are_redacted() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            "set-cookie",
+            HeaderValue::from_static("tutti_csrftoken=abc123; Path=/; Secure"),
+        );
+        headers.append(
+            "cookie",
+            HeaderValue::from_static("tutti_csrftoken=abc123; session=xyz"),
+        );
+        headers.append("x-csrf-token", HeaderValue::from_static("abc123"));
+        headers.append("accept-language", HeaderValue::from_static("de-CH"));
+
+        let redacted = redact_headers(&headers);
+        assert_eq!(
+            redacted,
+            [
+                (
+                    "set-cookie".to_string(),
+                    "tutti_csrftoken=redacted; Path=/; Secure".to_string()
+                ),
+                (
+                    "cookie".to_string(),
+                    "tutti_csrftoken=redacted; session=redacted".to_string()
+                ),
+                ("x-csrf-token".to_string(), "redacted".to_string()),
+                ("accept-language".to_string(), "de-CH".to_string()),
+            ]
+        );
+        assert!(!format!("{:?}", redacted).contains("abc123"));
+    }
+
+    #[test]
+    fn test_tokens_in_the_body_are_redacted() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            "set-cookie",
+            HeaderValue::from_static("tutti_csrftoken=f00dcafe42; Path=/"),
+        );
+        headers.append("set-cookie", HeaderValue::from_static("lang=de"));
+        let body = r#"<html lang="de"><script>window.csrf = "f00dcafe42";</script></html>"#;
+        assert_eq!(
+            redact_body(body, &headers),
+            r#"<html lang="de"><script>window.csrf = "redacted";</script></html>"#
+        );
+    }
+
+    #[test]
+    fn test_recording_is_saved_once() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("cassette.json");
+        let interaction = |url: &str| Interaction {
+            request: RecordedRequest {
+                method: "GET".to_string(),
+                url: url.to_string(),
+                headers: Vec::new(),
+                body: None,
+            },
+            response: RecordedResponse {
+                status: 200,
+                headers: Vec::new(),
+                body: String::new(),
+            },
+        };
+
+        let cassette = Cassette::record(&path);
+        cassette.push(interaction("https://www.tutti.ch/"));
+        assert!(!path.exists());
+        cassette.save().unwrap();
+        assert_eq!(Cassette::replay(&path).unwrap().interactions().len(), 1);
+
+        // Dropping the last clone saves what was recorded since
+        let clone = cassette.clone();
+        drop(cassette);
+        clone.push(interaction("https://www.tutti.ch/api/v10/graphql"));
+        drop(clone);
+        assert_eq!(Cassette::replay(&path).unwrap().interactions().len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+use crate::batch::SearchRequest;
+use crate::client::Session;
+use crate::errors::FetchListingsError;
+use crate::graphql::{CategoriesData, CategoryNode, ListingNode, CATEGORIES_QUERY};
+use crate::SearchConfig;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// A category with its subcategories.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Category {
+    /// Passed as `category` to searches.
+    pub id: String,
+    pub name: String,
+    pub children: Vec<Category>,
+}
+
+impl Category {
+    fn new(node: CategoryNode) -> Self {
+        Category {
+            id: node.categoryID,
+            name: node.label,
+            children: node.children.into_iter().map(Category::new).collect(),
+        }
+    }
+}
+
+/// All categories, with the time they were fetched so a cached copy can
+/// be refreshed.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CategoryTree {
+    pub roots: Vec<Category>,
+    pub fetched_at: Option<DateTime<Utc>>,
+}
+
+impl CategoryTree {
+    /// Loads a cached tree, empty if it does not exist yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(CategoryTree::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Whether the tree was fetched less than `max_age` ago.
+    pub fn is_fresh(&self, max_age: Duration) -> bool {
+        match (self.fetched_at, chrono::Duration::from_std(max_age)) {
+            (Some(fetched_at), Ok(max_age)) => Utc::now() - fetched_at < max_age,
+            _ => false,
+        }
+    }
+
+    /// Every category depth first, with its depth starting at 0.
+    pub fn flatten(&self) -> Vec<(usize, &Category)> {
+        fn visit<'a>(
+            categories: &'a [Category],
+            depth: usize,
+            out: &mut Vec<(usize, &'a Category)>,
+        ) {
+            for category in categories {
+                out.push((depth, category));
+                visit(&category.children, depth + 1, out);
+            }
+        }
+        let mut out = Vec::new();
+        visit(&self.roots, 0, &mut out);
+        out
+    }
+
+    pub fn find(&self, id: &str) -> Option<&Category> {
+        self.flatten()
+            .into_iter()
+            .map(|(_, category)| category)
+            .find(|category| category.id == id)
+    }
+
+    /// The categories from the top level down to `id`, e.g. for
+    /// "Fahrzeuge › Velos". Empty if there is no such category.
+    pub fn path(&self, id: &str) -> Vec<&Category> {
+        fn search<'a>(categories: &'a [Category], id: &str, path: &mut Vec<&'a Category>) -> bool {
+            for category in categories {
+                path.push(category);
+                if category.id == id || search(&category.children, id, path) {
+                    return true;
+                }
+                path.pop();
+            }
+            false
+        }
+        let mut path = Vec::new();
+        search(&self.roots, id, &mut path);
+        path
+    }
+}
+
+/// Fetches the category tree.
+pub async fn fetch_categories(config: &SearchConfig) -> Result<CategoryTree, FetchListingsError> {
+    Session::start(config).await?.categories().await
+}
+
+/// Returns the tree cached at `path` if it is younger than `max_age`, else
+/// fetches and caches it. If fetching fails, an outdated cache is returned
+/// instead of the error.
+pub async fn cached_categories(
+    path: &Path,
+    max_age: Duration,
+    config: &SearchConfig,
+) -> Result<CategoryTree, FetchListingsError> {
+    let cached = CategoryTree::load(path)?;
+    if cached.is_fresh(max_age) {
+        return Ok(cached);
+    }
+    match fetch_categories(config).await {
+        Ok(tree) => {
+            tree.save(path)?;
+            Ok(tree)
+        }
+        Err(_) if !cached.roots.is_empty() => Ok(cached),
+        Err(e) => Err(e),
+    }
+}
+
+/// Fetches all listings of a category, newest first.
+pub async fn fetch_category_listings(
+    category_id: &str,
+    config: &SearchConfig,
+) -> Result<Vec<ListingNode>, FetchListingsError> {
+    Session::start(config)
+        .await?
+        .fetch(&SearchRequest::browse(category_id))
+        .await
+}
+
+impl Session {
+    /// Like `fetch_categories`, over this session.
+    pub async fn categories(&self) -> Result<CategoryTree, FetchListingsError> {
+        let data: CategoriesData = self.execute(CATEGORIES_QUERY, json!({})).await?;
+        Ok(CategoryTree {
+            roots: data.categories.into_iter().map(Category::new).collect(),
+            fetched_at: Some(Utc::now()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> CategoryTree {
+        let data: CategoriesData = serde_json::from_value(json!({
+            "categories": [
+                { "categoryID": "vehicles", "label": "Fahrzeuge", "children": [
+                    { "categoryID": "bikes", "label": "Velos", "children": [
+                        { "categoryID": "ebikes", "label": "E-Bikes" }
+                    ]},
+                    { "categoryID": "cars", "label": "Autos" }
+                ]},
+                { "categoryID": "home", "label": "Haushalt" }
+            ]
+        }))
+        .unwrap();
+        CategoryTree {
+            roots: data.categories.into_iter().map(Category::new).collect(),
+            fetched_at: Some(Utc::now()),
+        }
+    }
+
+    #[test]
+    fn test_tree_lookups() {
+        let tree = tree();
+        let flat: Vec<(usize, &str)> = tree
+            .flatten()
+            .into_iter()
+            .map(|(depth, c)| (depth, c.id.as_str()))
+            .collect();
+        assert_eq!(
+            flat,
+            [
+                (0, "vehicles"),
+                (1, "bikes"),
+                (2, "ebikes"),
+                (1, "cars"),
+                (0, "home")
+            ]
+        );
+        assert_eq!(tree.find("cars").unwrap().name, "Autos");
+        let path: Vec<&str> = tree
+            .path("ebikes")
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(path, ["Fahrzeuge", "Velos", "E-Bikes"]);
+        assert!(tree.path("boats").is_empty());
+    }
+
+    #[test]
+    fn test_freshness() {
+        let mut tree = tree();
+        assert!(tree.is_fresh(Duration::from_secs(3600)));
+        tree.fetched_at = Some(Utc::now() - chrono::Duration::days(2));
+        assert!(!tree.is_fresh(Duration::from_secs(24 * 3600)));
+        assert!(!CategoryTree::default().is_fresh(Duration::from_secs(3600)));
+    }
+}
@@ -1,24 +1,521 @@
-use crate::graphql::{GraphQLResponse, ListingNode, FIRST, GRAPHQL_QUERY};
+use crate::batch::SearchRequest;
+use crate::cache::{Freshness, ResponseCache};
+use crate::cassette::{
+    redact_body, redact_headers, Cassette, CassetteMode, Interaction, RecordedRequest,
+    RecordedResponse, REDACTED,
+};
+use crate::errors::FetchListingsError;
+use crate::graphql::{
+    GraphQLData, GraphQLError, GraphQLResponse, ListingData, ListingNode, Listings, ParseWarning,
+    FIRST, GRAPHQL_QUERY, LISTING_QUERY,
+};
+use crate::locale::Locale;
+use crate::session::StoredSession;
 use crate::util::init_headers;
+use crate::SearchConfig;
 use reqwest::cookie::{CookieStore, Jar};
 use reqwest::{
-    header::{HeaderValue, CONTENT_TYPE, REFERER},
-    Client,
+    header::{
+        HeaderMap, HeaderValue, ACCEPT_LANGUAGE, CONTENT_TYPE, REFERER, RETRY_AFTER, SET_COOKIE,
+        USER_AGENT,
+    },
+    Client, Request, RequestBuilder, StatusCode,
 };
+use serde::de::DeserializeOwned;
 use serde_json::json;
-use std::error::Error;
-use std::sync::Arc;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{RwLock, Semaphore};
 use uuid::Uuid;
 
+/// The cookies and CSRF token of one visit to tutti.ch, shared by any number
+/// of searches so each of them does not pay for its own handshake.
+#[derive(Debug, Clone)]
+pub struct Session {
+    transport: Transport,
+    cookie_store: Arc<Jar>,
+    auth: Arc<RwLock<Auth>>,
+    config: SearchConfig,
+    permits: Option<Arc<Semaphore>>,
+    warnings: Arc<Mutex<Vec<ParseWarning>>>,
+}
+
+#[derive(Debug)]
+struct Auth {
+    csrf_token: String,
+    // Counts handshakes, so concurrent requests rejected with the same token
+    // renew it only once
+    generation: u64,
+}
+
+impl Session {
+    /// Starts a session with a handshake, or reuses the one saved in
+    /// `SearchConfig::session_file` if it has not expired. A reused session
+    /// is only checked by the first request; if tutti.ch rejects it, a new
+    /// handshake is made and the request is sent again.
+    pub async fn start(config: &SearchConfig) -> Result<Self, FetchListingsError> {
+        let cookie_store = Arc::new(Jar::default());
+        let mut builder = Client::builder().cookie_provider(cookie_store.clone());
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let builder = config.client.apply(builder)?;
+        let transport = Transport::new(
+            builder.build()?,
+            config.cassette.clone(),
+            config.cache.clone(),
+        )
+        .with_user_agent(config.client.pick_user_agent()?);
+        let stored = config
+            .session_file
+            .as_deref()
+            .and_then(|path| StoredSession::load(path).ok().flatten())
+            .filter(|stored| !stored.is_expired());
+        let csrf_token = match stored {
+            Some(stored) => {
+                stored.restore(&cookie_store);
+                stored.csrf_token
+            }
+            None => {
+                let stored = init_session(&transport, &cookie_store, config.locale).await?;
+                save_session(config, &transport, &stored);
+                stored.csrf_token
+            }
+        };
+        Ok(Session {
+            transport,
+            cookie_store,
+            auth: Arc::new(RwLock::new(Auth {
+                csrf_token,
+                generation: 0,
+            })),
+            config: config.clone(),
+            permits: None,
+            warnings: Arc::default(),
+        })
+    }
+
+    // Sends a request with the CSRF token, and once more with a new one if
+    // the token was rejected
+    async fn authorized<T, F, Fut>(&self, request: F) -> Result<T, FetchListingsError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T, FetchListingsError>>,
+    {
+        let (csrf_token, generation) = {
+            let auth = self.auth.read().await;
+            (auth.csrf_token.clone(), auth.generation)
+        };
+        match request(csrf_token).await {
+            Err(e) if e.is_session_rejected() => request(self.renew(generation).await?).await,
+            result => result,
+        }
+    }
+
+    // A new handshake, unless another request made one since `generation`
+    async fn renew(&self, generation: u64) -> Result<String, FetchListingsError> {
+        let mut auth = self.auth.write().await;
+        if auth.generation == generation {
+            let stored =
+                init_session(&self.transport, &self.cookie_store, self.config.locale).await?;
+            save_session(&self.config, &self.transport, &stored);
+            auth.csrf_token = stored.csrf_token;
+            auth.generation += 1;
+        }
+        Ok(auth.csrf_token.clone())
+    }
+
+    /// Refreshes stale cache entries before answering instead of in the
+    /// background, for callers whose runtime does not outlive the request.
+    pub fn with_inline_revalidation(mut self) -> Self {
+        self.transport = self.transport.with_inline_revalidation(true);
+        self
+    }
+
+    /// Limits the number of requests in flight across all searches of this
+    /// session and its clones.
+    pub fn with_concurrency(mut self, max_requests: usize) -> Self {
+        self.permits = Some(Arc::new(Semaphore::new(max_requests.max(1))));
+        self
+    }
+
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    /// Listings left out of results since the last call because they could
+    /// not be parsed, across this session and its clones.
+    pub fn take_warnings(&self) -> Vec<ParseWarning> {
+        std::mem::take(&mut self.warnings.lock().unwrap())
+    }
+
+    pub(crate) fn record_warnings(&self, warnings: Vec<ParseWarning>) {
+        if !warnings.is_empty() {
+            self.warnings.lock().unwrap().extend(warnings);
+        }
+    }
+
+    /// Fetches all pages of a query, up to `max_pages` of the config.
+    pub async fn search(&self, search_query: &str) -> Result<Vec<ListingNode>, FetchListingsError> {
+        self.fetch(&SearchRequest::new(search_query)).await
+    }
+
+    /// Fetches the listings of a request, up to its `max_pages` or else
+    /// those of the config.
+    pub async fn fetch(
+        &self,
+        request: &SearchRequest,
+    ) -> Result<Vec<ListingNode>, FetchListingsError> {
+        let (total_count, first_page_listings) = self.page(request, 0).await?;
+
+        let mut all_listings = first_page_listings;
+        let mut total_pages = total_count.div_ceil(FIRST) as usize;
+        if let Some(max_pages) = request.max_pages.or(self.config.max_pages) {
+            total_pages = total_pages.min(max_pages);
+        }
+
+        // Fetch remaining pages concurrently
+        let results = futures::future::join_all(
+            (1..total_pages).map(|page| self.page(request, page as u32 * FIRST)),
+        )
+        .await;
+        for result in results {
+            let (_, listings) = result?;
+            all_listings.extend(listings);
+        }
+
+        if let Some(filter) = &self.config.distance {
+            all_listings = filter.apply(all_listings);
+        }
+        Ok(all_listings)
+    }
+
+    async fn page(
+        &self,
+        request: &SearchRequest,
+        offset: u32,
+    ) -> Result<(u32, Vec<ListingNode>), FetchListingsError> {
+        let _permit = match &self.permits {
+            Some(permits) => Some(permits.acquire().await.expect("never closed")),
+            None => None,
+        };
+        let listings = self
+            .authorized(|csrf_token| async move {
+                perform_request(
+                    &self.transport,
+                    &csrf_token,
+                    self.config
+                        .search_document
+                        .as_deref()
+                        .unwrap_or(GRAPHQL_QUERY),
+                    Some(request.query.as_str()).filter(|query| !query.trim().is_empty()),
+                    request.category.as_deref(),
+                    offset,
+                    self.config.locale,
+                )
+                .await
+            })
+            .await?;
+        self.record_warnings(listings.warnings);
+        Ok((
+            listings.totalCount,
+            listings.edges.into_iter().map(|edge| edge.node).collect(),
+        ))
+    }
+
+    /// Fetches one listing by its ID, e.g. to refresh a listing found
+    /// earlier. Fails with `NotFound` if it no longer exists.
+    pub async fn listing(&self, listing_id: &str) -> Result<ListingNode, FetchListingsError> {
+        let data: ListingData = self
+            .execute(LISTING_QUERY, json!({ "listingID": listing_id }))
+            .await?;
+        data.listing
+            .ok_or_else(|| FetchListingsError::NotFound(format!("listing {}", listing_id)))
+    }
+
+    /// Runs any GraphQL document and deserializes its `data`, e.g. to
+    /// select fields the library does not know about yet. Counts towards
+    /// the concurrency limit like searches.
+    pub async fn execute<T: DeserializeOwned>(
+        &self,
+        document: &str,
+        variables: serde_json::Value,
+    ) -> Result<T, FetchListingsError> {
+        let _permit = match &self.permits {
+            Some(permits) => Some(permits.acquire().await.expect("never closed")),
+            None => None,
+        };
+        let referer = format!("https://www.tutti.ch/{}", self.config.locale.code());
+        self.authorized(|csrf_token| {
+            let (referer, variables) = (&referer, variables.clone());
+            async move {
+                post_graphql(
+                    &self.transport,
+                    &csrf_token,
+                    self.config.locale,
+                    referer,
+                    document,
+                    variables,
+                )
+                .await
+            }
+        })
+        .await
+    }
+}
+
+/// Sends requests over the network, or through a cassette if one is set.
+/// GraphQL requests are answered from the cache if one is set.
+#[derive(Debug, Clone)]
+pub struct Transport {
+    client: Client,
+    cassette: Option<Cassette>,
+    cache: Option<ResponseCache>,
+    // Replaces the user agent of every request
+    user_agent: Option<HeaderValue>,
+    // Refreshes stale responses before returning them
+    revalidate_inline: bool,
+}
+
+/// A response with its body read.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl HttpResponse {
+    fn cached(body: String) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        HttpResponse {
+            status: StatusCode::OK,
+            headers,
+            body,
+        }
+    }
+}
+
+impl Transport {
+    pub fn new(client: Client, cassette: Option<Cassette>, cache: Option<ResponseCache>) -> Self {
+        Transport {
+            client,
+            cassette,
+            cache,
+            user_agent: None,
+            revalidate_inline: false,
+        }
+    }
+
+    /// Sends `user_agent` with every request instead of the default one.
+    pub fn with_user_agent(mut self, user_agent: Option<HeaderValue>) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+
+    /// Refreshes stale cache entries before answering, falling back to the
+    /// stale response if that fails, instead of in a background task.
+    pub fn with_inline_revalidation(mut self, inline: bool) -> Self {
+        self.revalidate_inline = inline;
+        self
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    fn is_replaying(&self) -> bool {
+        self.cassette
+            .as_ref()
+            .is_some_and(|cassette| cassette.mode() == CassetteMode::Replay)
+    }
+
+    fn is_offline(&self) -> bool {
+        self.cache.as_ref().is_some_and(|cache| cache.offline)
+    }
+
+    pub async fn send(&self, request: RequestBuilder) -> Result<HttpResponse, FetchListingsError> {
+        let mut request = request.build()?;
+        if let Some(user_agent) = &self.user_agent {
+            request.headers_mut().insert(USER_AGENT, user_agent.clone());
+        }
+        let Some((cache, key)) = self.cache_key(&request) else {
+            if self.is_offline() {
+                return Err(FetchListingsError::Offline(format!(
+                    "{} is not a cached GraphQL request",
+                    request.url()
+                )));
+            }
+            return self.fetch(request).await;
+        };
+
+        // A cache that cannot be read is treated like an empty one
+        if let Some(cached) = cache.get(&key).ok().flatten() {
+            match cache.freshness(&cached) {
+                _ if cache.offline => return Ok(HttpResponse::cached(cached.body)),
+                Freshness::Fresh => return Ok(HttpResponse::cached(cached.body)),
+                Freshness::Stale if self.revalidate_inline => {
+                    return match self.fetch_into(cache, &key, request).await {
+                        Ok(response) if response.status.is_success() => Ok(response),
+                        _ => Ok(HttpResponse::cached(cached.body)),
+                    };
+                }
+                Freshness::Stale => {
+                    if let Some(request) = request.try_clone() {
+                        let transport = self.clone();
+                        tokio::spawn(
+                            async move { transport.fetch_into(cache, &key, request).await },
+                        );
+                    }
+                    return Ok(HttpResponse::cached(cached.body));
+                }
+                Freshness::Expired => {}
+            }
+        }
+        if cache.offline {
+            return Err(FetchListingsError::Offline(format!(
+                "no cached response to {}",
+                request.url()
+            )));
+        }
+        self.fetch_into(cache, &key, request).await
+    }
+
+    // GraphQL requests are cached by their document, variables and locale
+    fn cache_key(&self, request: &Request) -> Option<(ResponseCache, String)> {
+        let cache = self.cache.clone()?;
+        let body: serde_json::Value = serde_json::from_slice(request.body()?.as_bytes()?).ok()?;
+        let locale = request
+            .headers()
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let key = ResponseCache::key(body.get("query")?.as_str()?, &body["variables"], locale);
+        Some((cache, key))
+    }
+
+    // Fetches and caches the response if it has data
+    async fn fetch_into(
+        &self,
+        cache: ResponseCache,
+        key: &str,
+        request: Request,
+    ) -> Result<HttpResponse, FetchListingsError> {
+        let response = self.fetch(request).await?;
+        let has_data = response.status.is_success()
+            && serde_json::from_str::<GraphQLResponse<serde_json::Value>>(&response.body)
+                .is_ok_and(|parsed| parsed.data.is_some() && parsed.errors.is_none());
+        if has_data {
+            // Failing to cache is no reason to fail the request
+            let _ = cache.put(key, &response.body);
+        }
+        Ok(response)
+    }
+
+    async fn fetch(&self, request: Request) -> Result<HttpResponse, FetchListingsError> {
+        let recorded = record_request(&request);
+        match &self.cassette {
+            Some(cassette) if cassette.mode() == CassetteMode::Replay => {
+                let response = cassette.find(&recorded).ok_or_else(|| {
+                    FetchListingsError::CassetteError(format!(
+                        "no recorded response to {} {} in {}",
+                        recorded.method,
+                        recorded.url,
+                        cassette.path().display()
+                    ))
+                })?;
+                Ok(HttpResponse {
+                    status: StatusCode::from_u16(response.status).map_err(|_| {
+                        FetchListingsError::CassetteError(format!(
+                            "invalid status {}",
+                            response.status
+                        ))
+                    })?,
+                    headers: response.header_map(),
+                    body: response.body,
+                })
+            }
+            cassette => {
+                let response = self.client.execute(request).await?;
+                let status = response.status();
+                let headers = response.headers().clone();
+                let body = response.text().await?;
+                if let Some(cassette) = cassette {
+                    let response = RecordedResponse {
+                        status: status.as_u16(),
+                        headers: redact_headers(&headers),
+                        body: redact_body(&body, &headers),
+                    };
+                    cassette.push(Interaction {
+                        request: recorded,
+                        response,
+                    });
+                }
+                Ok(HttpResponse {
+                    status,
+                    headers,
+                    body,
+                })
+            }
+        }
+    }
+}
+
+// Only sessions from tutti.ch itself are worth saving. Failing to save is
+// no reason to fail the search.
+fn save_session(config: &SearchConfig, transport: &Transport, stored: &StoredSession) {
+    if let Some(path) = &config.session_file {
+        if !transport.is_replaying() && !transport.is_offline() {
+            let _ = stored.save(path);
+        }
+    }
+}
+
+fn record_request(request: &Request) -> RecordedRequest {
+    RecordedRequest {
+        method: request.method().to_string(),
+        url: request.url().to_string(),
+        headers: redact_headers(request.headers()),
+        body: request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned()),
+    }
+}
+
+/// Visits tutti.ch for its cookies and CSRF token.
 pub async fn init_session(
-    client: &Client,
+    transport: &Transport,
     cookie_store: &Arc<Jar>,
-) -> Result<String, Box<dyn Error + Send + Sync>> {
-    client
-        .get("https://www.tutti.ch")
-        .headers(init_headers())
-        .send()
+    locale: Locale,
+) -> Result<StoredSession, FetchListingsError> {
+    // Nothing is sent in offline mode, so no token is needed
+    if transport.is_offline() {
+        return Ok(StoredSession::capture(
+            cookie_store,
+            REDACTED.to_string(),
+            &HeaderMap::new(),
+        ));
+    }
+    let response = transport
+        .send(
+            transport
+                .get("https://www.tutti.ch")
+                .headers(init_headers(locale)),
+        )
         .await?;
+    if !response.status.is_success() {
+        return Err(FetchListingsError::from_status(
+            response.status,
+            retry_after(&response.headers),
+        ));
+    }
 
     let url = "https://www.tutti.ch/".parse().unwrap();
     let cookies = cookie_store
@@ -26,56 +523,102 @@ pub async fn init_session(
         .map(|cookies| cookies.to_str().unwrap_or("").to_string())
         .unwrap_or_default();
 
-    let csrf_token = cookies
-        .split(';')
+    // The token just set takes precedence over one restored into the jar.
+    // A replayed response only sets its cookies in the headers, if at all.
+    let set_cookies = response
+        .headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or_default());
+    let csrf_token = set_cookies
+        .chain(cookies.split(';'))
         .find_map(|cookie| {
-            let cookie = cookie.trim();
-            if cookie.starts_with("tutti_csrftoken=") {
-                Some(cookie["tutti_csrftoken=".len()..].to_string())
-            } else {
-                None
-            }
+            cookie
+                .trim()
+                .strip_prefix("tutti_csrftoken=")
+                .map(str::to_string)
         })
-        .ok_or("Failed to obtain CSRF token")?;
+        .or_else(|| transport.is_replaying().then(|| REDACTED.to_string()))
+        .ok_or_else(|| {
+            FetchListingsError::CsrfTokenError("no tutti_csrftoken cookie was set".to_string())
+        })?;
 
-    Ok(csrf_token)
+    Ok(StoredSession::capture(
+        cookie_store,
+        csrf_token,
+        &response.headers,
+    ))
 }
 
-pub async fn perform_request(
-    client: &Client,
-    csrf_token: &str,
-    search_query: &str,
+/// Variables of `GRAPHQL_QUERY` for one page. Without a query, all listings
+/// of the category are returned.
+pub fn search_variables(
+    search_query: Option<&str>,
+    category: Option<&str>,
     offset: u32,
-) -> Result<(u32, Vec<ListingNode>), Box<dyn Error + Send + Sync>> {
-    let x_tutti_hash = Uuid::new_v4().to_string();
-    let current_date = chrono::Utc::now().format("%Y-%m-%d-%H-%M").to_string();
-    let referer_hash = Uuid::new_v4().to_string().replace('-', "").to_lowercase();
-    let encoded_query = urlencoding::encode(search_query);
-
-    let variables = json!({
+) -> serde_json::Value {
+    json!({
         "query": search_query,
         "constraints": null,
-        "category": null,
+        "category": category,
         "first": FIRST,
         "offset": offset,
         "direction": "DESCENDING",
         "sort": "TIMESTAMP"
-    });
+    })
+}
+
+/// Fetches one page of listings with a search document like `GRAPHQL_QUERY`.
+/// Listings that cannot be parsed are left out and reported in `warnings`.
+pub async fn perform_request(
+    transport: &Transport,
+    csrf_token: &str,
+    document: &str,
+    search_query: Option<&str>,
+    category: Option<&str>,
+    offset: u32,
+    locale: Locale,
+) -> Result<Listings, FetchListingsError> {
+    let referer_hash = Uuid::new_v4().to_string().replace('-', "").to_lowercase();
+    let encoded_query = urlencoding::encode(search_query.unwrap_or_default());
+
+    let variables = search_variables(search_query, category, offset);
+
+    let referer = format!(
+        "https://www.tutti.ch{}/{}?sorting=newest&page=1&query={}",
+        locale.search_path(),
+        referer_hash,
+        encoded_query
+    );
+    let data: GraphQLData =
+        post_graphql(transport, csrf_token, locale, &referer, document, variables).await?;
+    Ok(data.searchListingsByQuery.listings)
+}
+
+/// Posts a GraphQL document to the tutti.ch API with the headers its web
+/// client sends, returning the `data` of the response.
+pub async fn post_graphql<T: DeserializeOwned>(
+    transport: &Transport,
+    csrf_token: &str,
+    locale: Locale,
+    referer: &str,
+    document: &str,
+    variables: serde_json::Value,
+) -> Result<T, FetchListingsError> {
+    let x_tutti_hash = Uuid::new_v4().to_string();
+    let current_date = chrono::Utc::now().format("%Y-%m-%d-%H-%M").to_string();
 
     let payload = json!({
-        "query": GRAPHQL_QUERY,
+        "query": document,
         "variables": variables
     });
 
-    let mut headers = init_headers();
+    let mut headers = init_headers(locale);
     headers.insert(
         REFERER,
-        format!(
-            "https://www.tutti.ch/de/q/suche/{}?sorting=newest&page=1&query={}",
-            referer_hash, encoded_query
-        )
-        .parse()
-        .unwrap(),
+        HeaderValue::from_str(referer)
+            .map_err(|_| FetchListingsError::InvalidRequest(format!("referer {}", referer)))?,
     );
     headers.insert(
         "X-Tutti-Hash",
@@ -94,35 +637,320 @@ pub async fn perform_request(
         .parse()
         .unwrap(),
     );
-    headers.insert("x-csrf-token", HeaderValue::from_str(csrf_token).unwrap());
+    headers.insert(
+        "x-csrf-token",
+        HeaderValue::from_str(csrf_token).map_err(|_| {
+            FetchListingsError::CsrfTokenError("the token is not a valid header".to_string())
+        })?,
+    );
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-    let response = client
-        .post("https://www.tutti.ch/api/v10/graphql")
-        .headers(headers)
-        .json(&payload)
-        .send()
-        .await?
-        .json::<GraphQLResponse>()
+    let response = transport
+        .send(
+            transport
+                .post("https://www.tutti.ch/api/v10/graphql")
+                .headers(headers)
+                .json(&payload),
+        )
         .await?;
+    parse_response(response.status, &response.headers, &response.body)
+}
+
+/// Turns a response of the GraphQL API into its `data`, or the most specific
+/// error: rate limiting, a challenge page, a rejected CSRF token, GraphQL
+/// errors, an HTTP status or where parsing failed.
+pub fn parse_response<T: DeserializeOwned>(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<T, FetchListingsError> {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(FetchListingsError::RateLimited {
+            retry_after: retry_after(headers),
+        });
+    }
+    let is_html = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"))
+        || body.trim_start().starts_with('<');
+    if is_html {
+        return Err(FetchListingsError::Challenge {
+            status,
+            title: html_title(body),
+        });
+    }
+
+    // GraphQL errors come with 200 as well as with 4xx statuses
+    let parsed = serde_path_to_error::deserialize::<_, GraphQLResponse<T>>(
+        &mut serde_json::Deserializer::from_str(body),
+    );
+    if let Ok(GraphQLResponse {
+        errors: Some(errors),
+        ..
+    }) = &parsed
+    {
+        if !errors.is_empty() {
+            return Err(graphql_error(errors.clone()));
+        }
+    }
+    if !status.is_success() {
+        if status == StatusCode::FORBIDDEN && body.to_lowercase().contains("csrf") {
+            return Err(FetchListingsError::CsrfTokenError(
+                "the API rejected the token".to_string(),
+            ));
+        }
+        return Err(FetchListingsError::from_status(status, None));
+    }
+    parsed?.data.ok_or_else(|| FetchListingsError::ParseError {
+        path: "data".to_string(),
+        message: "missing or null".to_string(),
+    })
+}
+
+fn graphql_error(errors: Vec<GraphQLError>) -> FetchListingsError {
+    if errors
+        .iter()
+        .any(|error| error.message.to_lowercase().contains("csrf"))
+    {
+        FetchListingsError::CsrfTokenError(errors[0].message.clone())
+    } else {
+        FetchListingsError::GraphQL(errors)
+    }
+}
+
+// Only the delay in seconds, not the HTTP date form
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+fn html_title(body: &str) -> Option<String> {
+    let start = body.find("<title>")? + "<title>".len();
+    let end = start + body[start..].find("</title>")?;
+    Some(body[start..end].trim().to_string()).filter(|title| !title.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphql::CategoriesData;
 
-    // Handle errors in the response
-    if let Some(errors) = response.errors {
-        return Err(format!("API returned errors: {}", errors).into());
+    fn parse(
+        status: u16,
+        content_type: &str,
+        body: &str,
+    ) -> Result<CategoriesData, FetchListingsError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("30"));
+        parse_response(StatusCode::from_u16(status).unwrap(), &headers, body)
     }
 
-    let data = response
-        .data
-        .ok_or("Empty data in response")?
-        .searchListingsByQuery
-        .listings;
+    #[test]
+    fn test_errors_are_classified() {
+        let json = "application/json";
+        assert!(parse(200, json, r#"{"data": {"categories": []}}"#).is_ok());
+        assert!(matches!(
+            parse(429, json, ""),
+            Err(FetchListingsError::RateLimited { retry_after: Some(d) }) if d.as_secs() == 30
+        ));
+        match parse(
+            403,
+            "text/html",
+            "<html><title>Just a moment...</title></html>",
+        ) {
+            Err(FetchListingsError::Challenge { status, title }) => {
+                assert_eq!(status, StatusCode::FORBIDDEN);
+                assert_eq!(title.as_deref(), Some("Just a moment..."));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            parse(403, json, r#"{"message": "CSRF token invalid"}"#),
+            Err(FetchListingsError::CsrfTokenError(_))
+        ));
+        assert!(matches!(
+            parse(404, json, "{}"),
+            Err(FetchListingsError::ClientError { status }) if status == StatusCode::NOT_FOUND
+        ));
+        assert!(matches!(
+            parse(502, json, ""),
+            Err(FetchListingsError::ServerError { .. })
+        ));
+
+        let errors = parse(
+            400,
+            json,
+            r#"{"errors": [{"message": "Cannot query field \"label\"", "path": ["categories", 0, "label"]}]}"#,
+        );
+        match errors {
+            Err(FetchListingsError::GraphQL(errors)) => {
+                assert_eq!(
+                    errors[0].to_string(),
+                    "categories[0].label: Cannot query field \"label\""
+                );
+            }
+            other => panic!("{:?}", other),
+        }
+
+        match parse(
+            200,
+            json,
+            r#"{"data": {"categories": [{"categoryID": 7}]}}"#,
+        ) {
+            Err(FetchListingsError::ParseError { path, .. }) => {
+                assert_eq!(path, "data.categories[0].categoryID");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            parse(200, json, r#"{"data": null}"#),
+            Err(FetchListingsError::ParseError { path, .. }) if path == "data"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_session_replays_a_cassette() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", Uuid::new_v4()));
+        let path = dir.join("cassette.json");
+        let search = json!({
+            "query": GRAPHQL_QUERY,
+            "variables": search_variables(Some("velo"), None, 0)
+        });
+        let listings = json!({ "data": { "searchListingsByQuery": { "listings": {
+            "totalCount": 1,
+            "edges": [{ "node": { "listingID": "42", "title": "Velo" } }]
+        }}}});
+        let interactions = json!({ "interactions": [
+            {
+                "request": { "method": "GET", "url": "https://www.tutti.ch/", "headers": [] },
+                "response": {
+                    "status": 200,
+                    "headers": [["set-cookie", "tutti_csrftoken=redacted; Path=/"]],
+                    "body": "<html></html>"
+                }
+            },
+            {
+                "request": {
+                    "method": "POST",
+                    "url": "https://www.tutti.ch/api/v10/graphql",
+                    "headers": [],
+                    "body": search.to_string()
+                },
+                "response": { "status": 200, "headers": [], "body": listings.to_string() }
+            }
+        ]});
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, interactions.to_string()).unwrap();
 
-    let total_count = data.totalCount;
-    let listings = data
-        .edges
-        .into_iter()
-        .map(|edge| edge.node)
-        .collect::<Vec<_>>();
+        let config = SearchConfig {
+            cassette: Some(Cassette::replay(&path).unwrap()),
+            ..SearchConfig::default()
+        };
+        let session = Session::start(&config).await.unwrap();
+        let found = session.search("velo").await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].listingID, "42");
+        assert!(matches!(
+            session.search("auto").await,
+            Err(FetchListingsError::CassetteError(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 
-    Ok((total_count, listings))
+    #[tokio::test]
+    async fn test_offline_session_serves_the_cache() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", Uuid::new_v4()));
+        let cache = ResponseCache {
+            offline: true,
+            ..ResponseCache::new(&dir)
+        };
+        let key = ResponseCache::key(
+            GRAPHQL_QUERY,
+            &search_variables(Some("velo"), None, 0),
+            Locale::De.accept_language(),
+        );
+        let listings = json!({ "data": { "searchListingsByQuery": { "listings": {
+            "totalCount": 1,
+            "edges": [{ "node": { "listingID": "42", "title": "Velo" } }]
+        }}}});
+        cache.put(&key, &listings.to_string()).unwrap();
+
+        let config = SearchConfig {
+            cache: Some(cache),
+            ..SearchConfig::default()
+        };
+        let session = Session::start(&config).await.unwrap();
+        assert_eq!(session.search("velo").await.unwrap()[0].listingID, "42");
+        assert!(matches!(
+            session.search("auto").await,
+            Err(FetchListingsError::Offline(_))
+        ));
+        assert!(matches!(
+            session
+                .transport
+                .send(session.transport.get("https://www.tutti.ch/"))
+                .await,
+            Err(FetchListingsError::Offline(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rejected_stored_session_is_renewed() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let session_file = dir.join("session.json");
+        let mut stored =
+            StoredSession::capture(&Jar::default(), "old".to_string(), &HeaderMap::new());
+        stored.cookies = vec!["tutti_csrftoken=old".to_string()];
+        stored.save(&session_file).unwrap();
+
+        let search = json!({
+            "query": GRAPHQL_QUERY,
+            "variables": search_variables(Some("velo"), None, 0)
+        })
+        .to_string();
+        let listings = json!({ "data": { "searchListingsByQuery": { "listings": {
+            "totalCount": 1,
+            "edges": [{ "node": { "listingID": "42" } }]
+        }}}});
+        let post = |status: u16, body: String| {
+            json!({
+                "request": { "method": "POST", "url": "https://www.tutti.ch/api/v10/graphql", "headers": [], "body": search },
+                "response": { "status": status, "headers": [], "body": body }
+            })
+        };
+        let cassette = json!({ "interactions": [
+            post(403, json!({ "message": "Invalid CSRF token" }).to_string()),
+            {
+                "request": { "method": "GET", "url": "https://www.tutti.ch/", "headers": [] },
+                "response": {
+                    "status": 200,
+                    "headers": [["set-cookie", "tutti_csrftoken=redacted; Path=/"]],
+                    "body": ""
+                }
+            },
+            post(200, listings.to_string())
+        ]});
+        let cassette_file = dir.join("cassette.json");
+        std::fs::write(&cassette_file, cassette.to_string()).unwrap();
+
+        let config = SearchConfig {
+            cassette: Some(Cassette::replay(&cassette_file).unwrap()),
+            session_file: Some(session_file),
+            ..SearchConfig::default()
+        };
+        // The stored session is used without a handshake
+        let session = Session::start(&config).await.unwrap();
+        assert_eq!(session.auth.read().await.csrf_token, "old");
+        assert_eq!(session.search("velo").await.unwrap()[0].listingID, "42");
+        assert_eq!(session.auth.read().await.csrf_token, REDACTED);
+        assert_eq!(session.auth.read().await.generation, 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
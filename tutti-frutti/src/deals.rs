@@ -0,0 +1,215 @@
+use crate::analytics::percentile;
+use crate::graphql::ListingNode;
+use crate::util::query_key;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Fewer historical prices than this make any score meaningless.
+pub const MIN_SAMPLE_SIZE: usize = 5;
+
+/// Prices seen for each query, keyed by listing so that listings showing up
+/// in several searches are only counted once.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PriceStore {
+    queries: HashMap<String, HashMap<String, f64>>,
+}
+
+impl PriceStore {
+    /// Loads a store from a JSON file, starting empty if it does not exist yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(PriceStore::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Remembers the price of every listing that has one.
+    pub fn record(&mut self, query: &str, listings: &[ListingNode]) {
+        let prices = self.queries.entry(query_key(query)).or_default();
+        for listing in listings {
+            if let Some(price) = listing.price() {
+                prices.insert(listing.listingID.clone(), price);
+            }
+        }
+    }
+
+    pub fn prices(&self, query: &str) -> Vec<f64> {
+        self.prices_excluding(query, &HashSet::new())
+    }
+
+    /// The prices of a query except those of `listing_ids`, e.g. of the
+    /// listings about to be scored, which should not count towards their
+    /// own history.
+    pub fn prices_excluding(&self, query: &str, listing_ids: &HashSet<&str>) -> Vec<f64> {
+        self.queries
+            .get(&query_key(query))
+            .map(|prices| {
+                prices
+                    .iter()
+                    .filter(|(id, _)| !listing_ids.contains(id.as_str()))
+                    .map(|(_, price)| *price)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DealVerdict {
+    /// So far below the usual price that it may be a scam, a typo or a
+    /// placeholder like "CHF 1.–".
+    SuspiciouslyLow,
+    Bargain,
+    Fair,
+    Expensive,
+}
+
+impl DealVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DealVerdict::SuspiciouslyLow => "Suspiciously low",
+            DealVerdict::Bargain => "Bargain",
+            DealVerdict::Fair => "Fair",
+            DealVerdict::Expensive => "Expensive",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DealScore {
+    pub price: f64,
+    /// Share of historical prices below this one, from 0 to 100.
+    pub percentile_rank: f64,
+    /// Distance from the historical mean in standard deviations.
+    pub z_score: f64,
+    pub verdict: DealVerdict,
+}
+
+/// Where the verdicts change.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DealThresholds {
+    /// Prices at or below this percentile rank are bargains.
+    pub bargain_percentile: f64,
+    /// Prices at or above this percentile rank are expensive.
+    pub expensive_percentile: f64,
+    /// Prices below this fraction of the median are suspicious.
+    pub suspicious_ratio: f64,
+}
+
+impl Default for DealThresholds {
+    fn default() -> Self {
+        DealThresholds {
+            bargain_percentile: 20.0,
+            expensive_percentile: 80.0,
+            suspicious_ratio: 0.3,
+        }
+    }
+}
+
+/// Scores prices against a historical price distribution.
+#[derive(Debug, Clone)]
+pub struct DealScorer {
+    sorted: Vec<f64>,
+    mean: f64,
+    std_dev: f64,
+    median: f64,
+    thresholds: DealThresholds,
+}
+
+impl DealScorer {
+    /// Returns `None` if there are fewer than `MIN_SAMPLE_SIZE` prices.
+    pub fn new(history: &[f64], thresholds: DealThresholds) -> Option<Self> {
+        let mut sorted: Vec<f64> = history.iter().copied().filter(|p| p.is_finite()).collect();
+        if sorted.len() < MIN_SAMPLE_SIZE {
+            return None;
+        }
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let n = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / n;
+        let variance = sorted.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let median = percentile(&sorted, 0.5);
+        Some(DealScorer {
+            sorted,
+            mean,
+            std_dev: variance.sqrt(),
+            median,
+            thresholds,
+        })
+    }
+
+    /// Scores a listing, or returns `None` if it has no price.
+    pub fn score(&self, listing: &ListingNode) -> Option<DealScore> {
+        listing.price().map(|price| self.score_price(price))
+    }
+
+    pub fn score_price(&self, price: f64) -> DealScore {
+        let below = self.sorted.partition_point(|&p| p < price);
+        let equal = self.sorted[below..].partition_point(|&p| p <= price);
+        let percentile_rank =
+            (below as f64 + equal as f64 / 2.0) / self.sorted.len() as f64 * 100.0;
+        let z_score = if self.std_dev > 0.0 {
+            (price - self.mean) / self.std_dev
+        } else {
+            0.0
+        };
+
+        let thresholds = &self.thresholds;
+        let verdict = if price < self.median * thresholds.suspicious_ratio {
+            DealVerdict::SuspiciouslyLow
+        } else if percentile_rank <= thresholds.bargain_percentile {
+            DealVerdict::Bargain
+        } else if percentile_rank >= thresholds.expensive_percentile {
+            DealVerdict::Expensive
+        } else {
+            DealVerdict::Fair
+        };
+
+        DealScore {
+            price,
+            percentile_rank,
+            z_score,
+            verdict,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdicts() {
+        let history: Vec<f64> = (1..=20).map(|p| p as f64 * 50.0).collect();
+        let scorer = DealScorer::new(&history, DealThresholds::default()).unwrap();
+
+        let cheap = scorer.score_price(200.0);
+        assert_eq!(cheap.verdict, DealVerdict::Bargain);
+        assert_eq!(cheap.percentile_rank, 17.5);
+        assert!(cheap.z_score < -1.0);
+
+        assert_eq!(scorer.score_price(500.0).verdict, DealVerdict::Fair);
+        assert_eq!(scorer.score_price(950.0).verdict, DealVerdict::Expensive);
+        assert_eq!(
+            scorer.score_price(60.0).verdict,
+            DealVerdict::SuspiciouslyLow
+        );
+    }
+
+    #[test]
+    fn test_small_samples_are_not_scored() {
+        assert!(DealScorer::new(&[10.0, 20.0, 30.0], DealThresholds::default()).is_none());
+    }
+}
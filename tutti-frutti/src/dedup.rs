@@ -0,0 +1,243 @@
+use crate::graphql::ListingNode;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// How similar two listings of the same seller must be to count as reposts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DedupOptions {
+    /// Minimum similarity of the normalized titles, from 0 to 1.
+    pub title_similarity: f64,
+    /// Minimum similarity of the descriptions, from 0 to 1.
+    pub body_similarity: f64,
+    /// Largest relative price difference, e.g. 0.1 for sellers lowering the
+    /// price by up to 10% when reposting.
+    pub price_tolerance: f64,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        DedupOptions {
+            title_similarity: 0.7,
+            body_similarity: 0.6,
+            price_tolerance: 0.15,
+        }
+    }
+}
+
+/// Listings that are most likely the same item posted several times.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RepostCluster {
+    /// Newest first. The first listing is the one kept when collapsing.
+    pub listing_ids: Vec<String>,
+}
+
+impl RepostCluster {
+    pub fn kept(&self) -> &str {
+        &self.listing_ids[0]
+    }
+
+    /// How often the item was reposted after first being listed.
+    pub fn reposts(&self) -> usize {
+        self.listing_ids.len() - 1
+    }
+}
+
+/// Groups listings of the same seller with similar title, description and
+/// price. Only clusters of at least two listings are returned. Listings
+/// without a seller alias are never clustered, since nothing tells whether
+/// they come from the same seller.
+pub fn find_reposts(listings: &[ListingNode], options: &DedupOptions) -> Vec<RepostCluster> {
+    let mut by_seller: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, listing) in listings.iter().enumerate() {
+        let alias = listing.sellerInfo.alias.trim();
+        if alias.is_empty() {
+            continue;
+        }
+        by_seller.entry(alias.to_lowercase()).or_default().push(i);
+    }
+
+    let fingerprints: Vec<Fingerprint> = listings.iter().map(Fingerprint::new).collect();
+    let mut parents: Vec<usize> = (0..listings.len()).collect();
+    for indices in by_seller.values() {
+        for (n, &a) in indices.iter().enumerate() {
+            for &b in &indices[n + 1..] {
+                if fingerprints[a].matches(&fingerprints[b], options) {
+                    union(&mut parents, a, b);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..listings.len() {
+        let root = find(&mut parents, i);
+        groups.entry(root).or_default().push(i);
+    }
+
+    let mut clusters: Vec<(usize, RepostCluster)> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|mut members| {
+            // Newest first, listings without a date last
+            members.sort_by_key(|&i| {
+                std::cmp::Reverse(listings[i].posted_at().map(|t| t.timestamp()))
+            });
+            let first = *members.iter().min().unwrap();
+            let listing_ids = members
+                .iter()
+                .map(|&i| listings[i].listingID.clone())
+                .collect();
+            (first, RepostCluster { listing_ids })
+        })
+        .collect();
+    // Keep the order of the input so results are stable
+    clusters.sort_by_key(|(first, _)| *first);
+    clusters.into_iter().map(|(_, cluster)| cluster).collect()
+}
+
+/// Drops every listing of a cluster except the one it keeps, leaving the
+/// order otherwise unchanged.
+pub fn collapse(listings: Vec<ListingNode>, clusters: &[RepostCluster]) -> Vec<ListingNode> {
+    let dropped: HashSet<&str> = clusters
+        .iter()
+        .flat_map(|c| c.listing_ids[1..].iter().map(String::as_str))
+        .collect();
+    listings
+        .into_iter()
+        .filter(|l| !dropped.contains(l.listingID.as_str()))
+        .collect()
+}
+
+/// Number of reposts by the ID of the listing kept for each cluster.
+pub fn repost_counts(clusters: &[RepostCluster]) -> HashMap<String, usize> {
+    clusters
+        .iter()
+        .map(|c| (c.kept().to_string(), c.reposts()))
+        .collect()
+}
+
+// What two listings are compared by
+struct Fingerprint {
+    title: HashSet<String>,
+    body: HashSet<String>,
+    price: Option<f64>,
+}
+
+impl Fingerprint {
+    fn new(listing: &ListingNode) -> Self {
+        let title = normalize(&listing.title);
+        // Character trigrams survive small edits like "Velo" vs "Velos"
+        let chars: Vec<char> = format!("  {} ", title).chars().collect();
+        Fingerprint {
+            title: chars.windows(3).map(|w| w.iter().collect()).collect(),
+            body: normalize(&listing.body)
+                .split(' ')
+                .filter(|word| !word.is_empty())
+                .map(str::to_string)
+                .collect(),
+            price: listing.price(),
+        }
+    }
+
+    fn matches(&self, other: &Fingerprint, options: &DedupOptions) -> bool {
+        let price_matches = match (self.price, other.price) {
+            (Some(a), Some(b)) => (a - b).abs() <= a.max(b) * options.price_tolerance,
+            (None, None) => true,
+            _ => false,
+        };
+        price_matches
+            && jaccard(&self.title, &other.title) >= options.title_similarity
+            && (self.body.is_empty() && other.body.is_empty()
+                || jaccard(&self.body, &other.body) >= options.body_similarity)
+    }
+}
+
+// Lowercase words separated by single spaces, without punctuation
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+pub(crate) fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+pub(crate) fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    parents[a.max(b)] = a.min(b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(id: &str, seller: &str, title: &str, body: &str, price: &str) -> ListingNode {
+        ListingNode::test(id)
+            .with_seller(seller)
+            .with_title(title)
+            .with_body(body)
+            .with_price(price)
+            .with_timestamp(&format!("2024-05-0{}T10:00:00+02:00", id))
+    }
+
+    #[test]
+    fn test_reposts_are_clustered_newest_first() {
+        let body = "Kaum gebraucht, mit Ladegerät und zwei Akkus. Nur Abholung.";
+        let listings = vec![
+            listing("1", "velofan", "E-Bike Flyer Upstreet", body, "CHF 1500.–"),
+            listing("2", "other", "E-Bike Flyer Upstreet", body, "CHF 1500.–"),
+            listing(
+                "3",
+                "VeloFan",
+                "E-Bike Flyer Upstreet!!",
+                body,
+                "CHF 1400.–",
+            ),
+            listing("4", "velofan", "Kinderwagen", "Gut erhalten", "CHF 1500.–"),
+            listing("5", "velofan", "e-bike flyer upstreet", body, "CHF 1450.–"),
+        ];
+        let clusters = find_reposts(&listings, &DedupOptions::default());
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].listing_ids, ["5", "3", "1"]);
+        assert_eq!(clusters[0].reposts(), 2);
+
+        let collapsed = collapse(listings, &clusters);
+        let ids: Vec<&str> = collapsed.iter().map(|l| l.listingID.as_str()).collect();
+        assert_eq!(ids, ["2", "4", "5"]);
+    }
+
+    #[test]
+    fn test_price_difference_splits_cluster() {
+        let listings = vec![
+            listing("1", "a", "Sofa", "Grau, drei Plätze", "CHF 300.–"),
+            listing("2", "a", "Sofa", "Grau, drei Plätze", "CHF 100.–"),
+        ];
+        assert!(find_reposts(&listings, &DedupOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_sellers_without_alias_are_not_clustered() {
+        let body = "Grau, drei Plätze, Abholung in Bern";
+        let listings = vec![
+            listing("1", "", "Sofa grau", body, "CHF 300.–"),
+            listing("2", "", "Sofa grau", body, "CHF 300.–"),
+        ];
+        assert!(find_reposts(&listings, &DedupOptions::default()).is_empty());
+    }
+}
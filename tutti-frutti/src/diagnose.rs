@@ -0,0 +1,327 @@
+use crate::batch::SearchRequest;
+use crate::client::{search_variables, Session};
+use crate::errors::FetchListingsError;
+use crate::graphql::{Listings, ParseWarning, GRAPHQL_QUERY};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// JSON type a field of the model is read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    String,
+    StringOrNumber,
+    Object,
+}
+
+impl FieldKind {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            FieldKind::String => value.is_string(),
+            FieldKind::StringOrNumber => value.is_string() || value.is_number(),
+            FieldKind::Object => value.is_object(),
+        }
+    }
+}
+
+/// A field of `ListingNode` as the crate expects it in a search response.
+#[derive(Debug, Clone, Copy)]
+pub struct ExpectedField {
+    /// Dot-separated path within `edges.node`, e.g. "sellerInfo.alias".
+    pub path: &'static str,
+    pub kind: FieldKind,
+    pub nullable: bool,
+}
+
+const fn field(path: &'static str, kind: FieldKind, nullable: bool) -> ExpectedField {
+    ExpectedField {
+        path,
+        kind,
+        nullable,
+    }
+}
+
+/// Everything `ListingNode` reads from a listing of `GRAPHQL_QUERY`.
+pub const LISTING_FIELDS: &[ExpectedField] = &[
+    field("listingID", FieldKind::String, false),
+    field("title", FieldKind::String, false),
+    field("body", FieldKind::String, false),
+    field("timestamp", FieldKind::String, false),
+    field("formattedPrice", FieldKind::String, true),
+    field("sellerInfo", FieldKind::Object, false),
+    field("sellerInfo.alias", FieldKind::String, false),
+    field("thumbnail", FieldKind::Object, true),
+    field("thumbnail.normalRendition", FieldKind::Object, true),
+    field("thumbnail.normalRendition.src", FieldKind::String, false),
+    field("postcodeInformation", FieldKind::Object, true),
+    field(
+        "postcodeInformation.postcode",
+        FieldKind::StringOrNumber,
+        false,
+    ),
+    field("postcodeInformation.locationName", FieldKind::String, false),
+    field("postcodeInformation.canton", FieldKind::Object, true),
+    field(
+        "postcodeInformation.canton.shortName",
+        FieldKind::String,
+        false,
+    ),
+    field("postcodeInformation.canton.name", FieldKind::String, false),
+];
+
+/// How often a field differed from the model, by listing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldReport {
+    pub missing: usize,
+    /// Null although the model does not allow it.
+    pub null: usize,
+    /// Another JSON type than expected, by the type found, e.g. "number".
+    pub changed: BTreeMap<&'static str, usize>,
+}
+
+impl FieldReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing == 0 && self.null == 0 && self.changed.is_empty()
+    }
+}
+
+/// Differences between a search response and the crate's model.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaReport {
+    /// Number of listings inspected.
+    pub listings: usize,
+    /// Problems outside the listings, e.g. a renamed `searchListingsByQuery`.
+    pub structure: Vec<String>,
+    /// Fields of `LISTING_FIELDS` that differed, by path.
+    pub fields: BTreeMap<&'static str, FieldReport>,
+    /// Fields the model does not know, by path, with the number of listings
+    /// that have them.
+    pub unknown_fields: BTreeMap<String, usize>,
+    /// Listings that are left out of results entirely.
+    pub warnings: Vec<ParseWarning>,
+}
+
+impl SchemaReport {
+    /// Whether the response matches the model, ignoring unknown fields.
+    pub fn is_ok(&self) -> bool {
+        self.structure.is_empty() && self.fields.is_empty() && self.warnings.is_empty()
+    }
+}
+
+impl fmt::Display for SchemaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Inspected {} listings", self.listings)?;
+        for problem in &self.structure {
+            writeln!(f, "  {}", problem)?;
+        }
+        for (path, report) in &self.fields {
+            let mut problems = Vec::new();
+            if report.missing > 0 {
+                problems.push(format!("missing in {}", report.missing));
+            }
+            if report.null > 0 {
+                problems.push(format!("null in {}", report.null));
+            }
+            for (kind, count) in &report.changed {
+                problems.push(format!("{} in {}", kind, count));
+            }
+            writeln!(f, "  {}: {}", path, problems.join(", "))?;
+        }
+        for (path, count) in &self.unknown_fields {
+            writeln!(f, "  {}: unknown field in {}", path, count)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "  Left out {}", warning)?;
+        }
+        if self.is_ok() {
+            writeln!(f, "  All fields match the model")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the `data` of a search response with the crate's model.
+pub fn diagnose_search(data: &Value) -> SchemaReport {
+    let mut report = SchemaReport::default();
+    let Some(listings) = data.pointer("/searchListingsByQuery/listings") else {
+        report
+            .structure
+            .push("searchListingsByQuery.listings is missing".to_string());
+        return report;
+    };
+    if !listings.get("totalCount").is_some_and(Value::is_u64) {
+        report
+            .structure
+            .push("listings.totalCount is missing or not a count".to_string());
+    }
+    let Some(edges) = listings.get("edges").and_then(Value::as_array) else {
+        report
+            .structure
+            .push("listings.edges is missing or not a list".to_string());
+        return report;
+    };
+
+    for (index, edge) in edges.iter().enumerate() {
+        match edge.get("node") {
+            Some(node) if node.is_object() => {
+                report.listings += 1;
+                check_listing(node, &mut report);
+            }
+            _ => report
+                .structure
+                .push(format!("edge {} has no listing node", index)),
+        }
+    }
+    if let Ok(parsed) = serde_json::from_value::<Listings>(listings.clone()) {
+        report.warnings = parsed.warnings;
+    }
+    report
+}
+
+fn check_listing(node: &Value, report: &mut SchemaReport) {
+    for expected in LISTING_FIELDS {
+        // A missing or null parent is reported on its own
+        let Some(parent) = lookup(node, parent_path(expected.path)).filter(|v| v.is_object())
+        else {
+            continue;
+        };
+        let name = expected.path.rsplit('.').next().unwrap_or(expected.path);
+        let fields = &mut report.fields;
+        match parent.get(name) {
+            None => fields.entry(expected.path).or_default().missing += 1,
+            Some(Value::Null) if expected.nullable => {}
+            Some(Value::Null) => fields.entry(expected.path).or_default().null += 1,
+            Some(value) if expected.kind.matches(value) => {}
+            Some(value) => {
+                *fields
+                    .entry(expected.path)
+                    .or_default()
+                    .changed
+                    .entry(json_type(value))
+                    .or_default() += 1
+            }
+        }
+    }
+
+    // Fields within the objects of the model it does not know about
+    let objects = std::iter::once("").chain(
+        LISTING_FIELDS
+            .iter()
+            .filter(|expected| expected.kind == FieldKind::Object)
+            .map(|expected| expected.path),
+    );
+    for object_path in objects {
+        let Some(object) = lookup(node, object_path).and_then(Value::as_object) else {
+            continue;
+        };
+        for key in object.keys() {
+            let path = if object_path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", object_path, key)
+            };
+            if !LISTING_FIELDS.iter().any(|expected| expected.path == path) {
+                *report.unknown_fields.entry(path).or_default() += 1;
+            }
+        }
+    }
+}
+
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once('.').map_or("", |(parent, _)| parent)
+}
+
+fn lookup<'a>(node: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.is_empty() {
+        return Some(node);
+    }
+    path.split('.')
+        .try_fold(node, |value, name| value.get(name))
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+impl Session {
+    /// Fetches the first page of a search as raw JSON and compares it with
+    /// the crate's model, to find out what changed when searches start
+    /// failing or returning empty fields.
+    pub async fn diagnose(
+        &self,
+        request: &SearchRequest,
+    ) -> Result<SchemaReport, FetchListingsError> {
+        let document = self
+            .config()
+            .search_document
+            .as_deref()
+            .unwrap_or(GRAPHQL_QUERY);
+        let query = Some(request.query.as_str()).filter(|query| !query.trim().is_empty());
+        let variables = search_variables(query, request.category.as_deref(), 0);
+        let data: Value = self.execute(document, variables).await?;
+        Ok(diagnose_search(&data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reports_drift_from_the_model() {
+        let report = diagnose_search(&json!({
+            "searchListingsByQuery": { "listings": {
+                "totalCount": 2,
+                "edges": [
+                    { "node": {
+                        "listingID": "1",
+                        "title": "Velo",
+                        "timestamp": "2024-05-01T10:00:00+02:00",
+                        "formattedPrice": null,
+                        "sellerInfo": { "alias": null, "sellerID": "9" },
+                        "thumbnail": null,
+                        "postcodeInformation": { "postcode": 8001, "locationName": "Zürich" },
+                        "isFeatured": true
+                    }},
+                    { "node": {
+                        "listingID": 2,
+                        "title": "Velo",
+                        "body": "",
+                        "timestamp": "2024-05-01T10:00:00+02:00",
+                        "sellerInfo": { "alias": "velofan" }
+                    }}
+                ]
+            }}
+        }));
+
+        assert_eq!(report.listings, 2);
+        assert!(report.structure.is_empty());
+        assert_eq!(report.fields["body"].missing, 1);
+        assert_eq!(report.fields["sellerInfo.alias"].null, 1);
+        assert_eq!(report.fields["listingID"].changed["number"], 1);
+        assert_eq!(report.fields["formattedPrice"].missing, 1);
+        // Null where allowed, and children of missing parents, are fine
+        assert!(!report.fields.contains_key("thumbnail.normalRendition.src"));
+        assert!(!report
+            .fields
+            .contains_key("postcodeInformation.canton.name"));
+        assert_eq!(report.unknown_fields["isFeatured"], 1);
+        assert_eq!(report.unknown_fields["sellerInfo.sellerID"], 1);
+        assert_eq!(report.warnings.len(), 1);
+        assert!(!report.is_ok());
+
+        let renamed = diagnose_search(&json!({ "searchListings": {} }));
+        assert_eq!(
+            renamed.structure,
+            ["searchListingsByQuery.listings is missing"]
+        );
+    }
+}
@@ -1,19 +1,90 @@
+use crate::graphql::GraphQLError;
 use reqwest::Error as ReqwestError;
+use reqwest::StatusCode;
 use std::error::Error;
 use std::fmt;
+use std::io;
+use std::time::Duration;
 use tokio::time::error::Elapsed;
 
 /// Represents errors that can occur while fetching listings.
 #[derive(Debug)]
 pub enum FetchListingsError {
-    /// An error occurred during an HTTP request.
+    /// An error occurred during an HTTP request, e.g. no connection.
     RequestError(ReqwestError),
     /// The request timed out.
     TimeoutError,
-    /// An error occurred while retrieving the CSRF token.
+    /// tutti.ch did not hand out a CSRF token, or the API rejected it.
     CsrfTokenError(String),
+    /// HTTP 429, with the delay the server asked for.
+    RateLimited { retry_after: Option<Duration> },
+    /// An HTML page where JSON was expected, usually a bot protection
+    /// challenge. Retrying right away rarely helps.
+    Challenge {
+        status: StatusCode,
+        /// The `<title>` of the page, if any.
+        title: Option<String>,
+    },
+    /// Any other 4xx status.
+    ClientError { status: StatusCode },
+    /// Any 5xx status.
+    ServerError { status: StatusCode },
+    /// The API answered with GraphQL errors, e.g. for a document that no
+    /// longer matches the schema.
+    GraphQL(Vec<GraphQLError>),
     /// An error occurred while parsing a response.
-    ParseError(String),
+    ParseError {
+        /// JSON path of the offending value, e.g. "data.searchListingsByQuery.listings.totalCount".
+        path: String,
+        message: String,
+    },
+    /// The request could not be built, e.g. because of an invalid header.
+    InvalidRequest(String),
+    /// A request that is not in the replayed cassette, or one that could not
+    /// be recorded.
+    CassetteError(String),
+    /// In offline mode, a request whose response is not cached.
+    Offline(String),
+    /// The API has nothing by that name, e.g. no seller with an alias.
+    NotFound(String),
+    /// Reading or writing a cache failed, or a downloaded image is invalid.
+    Io(io::Error),
+}
+
+impl FetchListingsError {
+    /// Classifies an unsuccessful status.
+    pub fn from_status(status: StatusCode, retry_after: Option<Duration>) -> Self {
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            FetchListingsError::RateLimited { retry_after }
+        } else if status.is_server_error() {
+            FetchListingsError::ServerError { status }
+        } else {
+            FetchListingsError::ClientError { status }
+        }
+    }
+
+    /// Whether the same request may succeed later: timeouts, rate limits and
+    /// server errors.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            FetchListingsError::RequestError(e) => e.is_connect(),
+            FetchListingsError::TimeoutError
+            | FetchListingsError::RateLimited { .. }
+            | FetchListingsError::ServerError { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether tutti.ch rejected the session, so a new handshake may help.
+    pub fn is_session_rejected(&self) -> bool {
+        match self {
+            FetchListingsError::CsrfTokenError(_) => true,
+            FetchListingsError::ClientError { status } => {
+                *status == StatusCode::UNAUTHORIZED || *status == StatusCode::FORBIDDEN
+            }
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Display for FetchListingsError {
@@ -22,7 +93,37 @@ impl fmt::Display for FetchListingsError {
             FetchListingsError::RequestError(e) => write!(f, "Request error: {}", e),
             FetchListingsError::TimeoutError => write!(f, "Request timed out"),
             FetchListingsError::CsrfTokenError(msg) => write!(f, "CSRF token error: {}", msg),
-            FetchListingsError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            FetchListingsError::RateLimited {
+                retry_after: Some(delay),
+            } => write!(f, "Rate limited, retry after {}s", delay.as_secs()),
+            FetchListingsError::RateLimited { retry_after: None } => write!(f, "Rate limited"),
+            FetchListingsError::Challenge { status, title } => {
+                write!(f, "Blocked by a challenge page ({})", status)?;
+                if let Some(title) = title {
+                    write!(f, ": {}", title)?;
+                }
+                Ok(())
+            }
+            FetchListingsError::ClientError { status } => write!(f, "Request rejected: {}", status),
+            FetchListingsError::ServerError { status } => write!(f, "Server error: {}", status),
+            FetchListingsError::GraphQL(errors) => {
+                write!(f, "API returned errors: ")?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+            FetchListingsError::ParseError { path, message } => {
+                write!(f, "Parse error at {}: {}", path, message)
+            }
+            FetchListingsError::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
+            FetchListingsError::CassetteError(msg) => write!(f, "Cassette error: {}", msg),
+            FetchListingsError::Offline(msg) => write!(f, "Offline: {}", msg),
+            FetchListingsError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            FetchListingsError::Io(e) => write!(f, "I/O error: {}", e),
         }
     }
 }
@@ -31,6 +132,7 @@ impl Error for FetchListingsError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             FetchListingsError::RequestError(e) => Some(e),
+            FetchListingsError::Io(e) => Some(e),
             _ => None,
         }
     }
@@ -38,7 +140,17 @@ impl Error for FetchListingsError {
 
 impl From<ReqwestError> for FetchListingsError {
     fn from(err: ReqwestError) -> FetchListingsError {
-        FetchListingsError::RequestError(err)
+        if err.is_timeout() {
+            FetchListingsError::TimeoutError
+        } else {
+            FetchListingsError::RequestError(err)
+        }
+    }
+}
+
+impl From<io::Error> for FetchListingsError {
+    fn from(err: io::Error) -> FetchListingsError {
+        FetchListingsError::Io(err)
     }
 }
 
@@ -47,3 +159,12 @@ impl From<Elapsed> for FetchListingsError {
         FetchListingsError::TimeoutError
     }
 }
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for FetchListingsError {
+    fn from(err: serde_path_to_error::Error<serde_json::Error>) -> FetchListingsError {
+        FetchListingsError::ParseError {
+            path: err.path().to_string(),
+            message: err.into_inner().to_string(),
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::graphql::ListingNode;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// Postcode, place and approximate coordinates of the town centre, sorted by postcode
+const POSTCODES_CSV: &str = include_str!("../data/postcodes.csv");
+
+/// A point on the map in decimal degrees.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Coordinates {
+    /// Great-circle distance in kilometres.
+    pub fn distance_km(&self, other: &Coordinates) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Place {
+    postcode: u16,
+    name: &'static str,
+    coordinates: Coordinates,
+}
+
+static PLACES: OnceLock<Vec<Place>> = OnceLock::new();
+
+fn places() -> &'static [Place] {
+    PLACES.get_or_init(|| parse_places(POSTCODES_CSV).unwrap_or_default())
+}
+
+// Finds the columns by name, so that besides the bundled format the official
+// directory of Swiss localities (swisstopo, CSV with WGS84 coordinates) can be
+// read as is. Keeps the first place of each postcode, sorted by postcode.
+fn parse_places(csv: &'static str) -> Option<Vec<Place>> {
+    let mut lines = csv.lines();
+    let header = lines.next()?.trim_start_matches('\u{feff}');
+    let delimiter = if header.contains(';') { ';' } else { ',' };
+    let columns: Vec<&str> = header.split(delimiter).map(str::trim).collect();
+    let column = |names: [&str; 2]| {
+        columns
+            .iter()
+            .position(|column| names.iter().any(|name| column.eq_ignore_ascii_case(name)))
+    };
+    let (postcode, name) = (
+        column(["postcode", "PLZ"])?,
+        column(["name", "Ortschaftsname"])?,
+    );
+    let (lat, lon) = (column(["lat", "N"])?, column(["lon", "E"])?);
+
+    let mut places: Vec<Place> = lines
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(delimiter).map(str::trim).collect();
+            Some(Place {
+                postcode: fields.get(postcode)?.parse().ok()?,
+                name: fields.get(name)?,
+                coordinates: Coordinates {
+                    lat: fields.get(lat)?.parse().ok()?,
+                    lon: fields.get(lon)?.parse().ok()?,
+                },
+            })
+        })
+        .collect();
+    places.sort_by_key(|place| place.postcode);
+    places.dedup_by_key(|place| place.postcode);
+    Some(places)
+}
+
+/// Replaces the bundled table, which only lists the larger places, with a
+/// complete one, e.g. the official directory of Swiss localities. The file
+/// needs postcode, name, lat and lon columns (or PLZ, Ortschaftsname, N and
+/// E), separated by commas or semicolons. Must be called before the first
+/// lookup. Returns the number of postcodes.
+pub fn load_postcode_table(path: &Path) -> io::Result<usize> {
+    // Lives as long as the table, which is never dropped
+    let csv: &'static str = Box::leak(fs::read_to_string(path)?.into_boxed_str());
+    let places = parse_places(csv)
+        .filter(|places| !places.is_empty())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected postcode, name, lat and lon columns",
+            )
+        })?;
+    let count = places.len();
+    PLACES
+        .set(places)
+        .map_err(|_| io::Error::other("postcodes were looked up before the table was loaded"))?;
+    Ok(count)
+}
+
+// The listed place for a postcode, or else the listed place with the closest
+// postcode sharing the first three digits, which are assigned to neighbouring
+// places. Beyond that, e.g. 3900 Brig and 3920 Zermatt, guesses get too far
+// off and the postcode is left unresolved.
+fn place(postcode: &str) -> Option<&'static Place> {
+    let postcode: u16 = postcode.trim().parse().ok()?;
+    if !(1000..=9999).contains(&postcode) {
+        return None;
+    }
+    let places = places();
+    if let Ok(index) = places.binary_search_by_key(&postcode, |p| p.postcode) {
+        return Some(&places[index]);
+    }
+    places
+        .iter()
+        .filter(|p| p.postcode / 10 == postcode / 10)
+        .min_by_key(|p| p.postcode.abs_diff(postcode))
+}
+
+/// Approximate coordinates of a Swiss postcode from the bundled table,
+/// `None` if the postcode is invalid or its area is not covered, see
+/// `load_postcode_table`.
+pub fn postcode_coordinates(postcode: &str) -> Option<Coordinates> {
+    place(postcode).map(|p| p.coordinates)
+}
+
+/// Name of the place used for a postcode, e.g. to confirm the home postcode.
+pub fn postcode_name(postcode: &str) -> Option<&'static str> {
+    place(postcode).map(|p| p.name)
+}
+
+impl ListingNode {
+    /// Approximate coordinates of the listing's postcode.
+    pub fn coordinates(&self) -> Option<Coordinates> {
+        postcode_coordinates(&self.postcodeInformation.as_ref()?.postcode)
+    }
+
+    /// Distance from `home` in kilometres, `None` if the location is unknown.
+    pub fn distance_km(&self, home: &Coordinates) -> Option<f64> {
+        self.coordinates().map(|c| c.distance_km(home))
+    }
+}
+
+/// Keeps only listings that can be picked up near home.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DistanceFilter {
+    pub home: Coordinates,
+    pub max_km: f64,
+    /// Keep listings whose location cannot be resolved.
+    pub keep_unknown: bool,
+}
+
+impl DistanceFilter {
+    /// A filter around a home postcode, `None` if the postcode is not known.
+    /// Listings whose location cannot be resolved are kept, since they may
+    /// well be nearby.
+    pub fn around(postcode: &str, max_km: f64) -> Option<Self> {
+        Some(DistanceFilter {
+            home: postcode_coordinates(postcode)?,
+            max_km,
+            keep_unknown: true,
+        })
+    }
+
+    pub fn matches(&self, listing: &ListingNode) -> bool {
+        match listing.distance_km(&self.home) {
+            Some(distance) => distance <= self.max_km,
+            None => self.keep_unknown,
+        }
+    }
+
+    pub fn apply(&self, listings: Vec<ListingNode>) -> Vec<ListingNode> {
+        listings.into_iter().filter(|l| self.matches(l)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_sorted_and_complete() {
+        let places = places();
+        assert_eq!(places.len(), POSTCODES_CSV.lines().count() - 1);
+        assert!(places.windows(2).all(|w| w[0].postcode < w[1].postcode));
+    }
+
+    #[test]
+    fn test_distances() {
+        let zurich = postcode_coordinates("8001").unwrap();
+        let bern = postcode_coordinates("3011").unwrap();
+        let distance = zurich.distance_km(&bern);
+        assert!((90.0..105.0).contains(&distance), "{}", distance);
+
+        // Not listed, resolved to a nearby Zürich postcode
+        assert_eq!(postcode_name("8044"), Some("Zürich"));
+        // Nothing listed with the same first three digits
+        assert_eq!(postcode_coordinates("8999"), None);
+        assert_eq!(postcode_coordinates("0999"), None);
+        assert_eq!(postcode_coordinates("abcd"), None);
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = DistanceFilter::around("8001", 30.0).unwrap();
+        assert!(filter.matches(&ListingNode::test("1").with_postcode("8400")));
+        assert!(!filter.matches(&ListingNode::test("1").with_postcode("3011")));
+        assert!(filter.matches(&ListingNode::test("1")));
+        let strict = DistanceFilter {
+            keep_unknown: false,
+            ..filter
+        };
+        assert!(!strict.matches(&ListingNode::test("1")));
+    }
+
+    #[test]
+    fn test_official_directory_format() {
+        let places = parse_places(
+            "\u{feff}Ortschaftsname;PLZ;Zusatzziffer;Gemeindename;E;N\n\
+             Zermatt;3920;0;Zermatt;7.7491;46.0207\n\
+             Brig;3900;0;Brig-Glis;7.9878;46.3159\n\
+             Gamsen;3900;2;Brig-Glis;7.9561;46.3036\n",
+        )
+        .unwrap();
+        let names: Vec<(u16, &str)> = places.iter().map(|p| (p.postcode, p.name)).collect();
+        assert_eq!(names, [(3900, "Brig"), (3920, "Zermatt")]);
+        assert_eq!(places[1].coordinates.lat, 46.0207);
+        assert!(parse_places("plz,ort\n8001,Zürich\n").is_none());
+    }
+}
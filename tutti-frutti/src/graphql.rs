@@ -1,4 +1,14 @@
+// Field names mirror the tutti.ch GraphQL schema.
+#![allow(non_snake_case)]
+
+use chrono::{DateTime, FixedOffset};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 
 pub const FIRST: u32 = 30;
 
@@ -26,6 +36,76 @@ query SearchListingsByConstraints($query: String, $constraints: ListingSearchCon
               src
             }
           }
+          postcodeInformation {
+            postcode
+            locationName
+            canton {
+              shortName
+              name
+            }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Reads a replacement for `GRAPHQL_QUERY` from a file, e.g. to follow a
+/// schema change without waiting for a release. The document must take the
+/// same variables and select `searchListingsByQuery.listings` with
+/// `totalCount` and the fields of `ListingNode` in `edges.node`.
+pub fn read_search_document(path: &Path) -> io::Result<String> {
+    let document = fs::read_to_string(path)?;
+    if !document.contains("searchListingsByQuery") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the document does not select searchListingsByQuery",
+        ));
+    }
+    Ok(document)
+}
+
+/// Public profile of a seller with a page of their listings, by alias or ID.
+pub const SELLER_LISTINGS_QUERY: &str = r#"
+query SellerListings($alias: String, $sellerID: ID, $first: Int!, $offset: Int!) {
+  sellerProfile(alias: $alias, sellerID: $sellerID) {
+    sellerID
+    alias
+    memberSince
+    postcodeInformation {
+      postcode
+      locationName
+      canton {
+        shortName
+        name
+      }
+    }
+    listings(first: $first, offset: $offset, sort: TIMESTAMP, direction: DESCENDING) {
+      totalCount
+      edges {
+        node {
+          listingID
+          title
+          body
+          timestamp
+          formattedPrice
+          sellerInfo {
+            alias
+          }
+          thumbnail {
+            normalRendition: rendition(width: 235, height: 167) {
+              src
+            }
+          }
+          postcodeInformation {
+            postcode
+            locationName
+            canton {
+              shortName
+              name
+            }
+          }
         }
       }
     }
@@ -33,10 +113,89 @@ query SearchListingsByConstraints($query: String, $constraints: ListingSearchCon
 }
 "#;
 
+/// A single listing by its ID, with the same fields as search results.
+pub const LISTING_QUERY: &str = r#"
+query Listing($listingID: ID!) {
+  listing(listingID: $listingID) {
+    listingID
+    title
+    body
+    timestamp
+    formattedPrice
+    sellerInfo {
+      alias
+    }
+    thumbnail {
+      normalRendition: rendition(width: 235, height: 167) {
+        src
+      }
+    }
+    postcodeInformation {
+      postcode
+      locationName
+      canton {
+        shortName
+        name
+      }
+    }
+  }
+}
+"#;
+
+/// The category tree, three levels deep.
+pub const CATEGORIES_QUERY: &str = r#"
+query Categories {
+  categories {
+    categoryID
+    label
+    children {
+      categoryID
+      label
+      children {
+        categoryID
+        label
+      }
+    }
+  }
+}
+"#;
+
 #[derive(Serialize, Deserialize, Debug)]
-pub struct GraphQLResponse {
-    pub data: Option<GraphQLData>,
-    pub errors: Option<serde_json::Value>,
+pub struct GraphQLResponse<T = GraphQLData> {
+    pub data: Option<T>,
+    pub errors: Option<Vec<GraphQLError>>,
+}
+
+/// An entry of the `errors` of a GraphQL response.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GraphQLError {
+    pub message: String,
+    /// The field of the response the error belongs to, if any.
+    #[serde(default)]
+    pub path: Vec<PathSegment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl fmt::Display for GraphQLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.path.iter().enumerate() {
+            match segment {
+                PathSegment::Field(name) if i == 0 => write!(f, "{}", name)?,
+                PathSegment::Field(name) => write!(f, ".{}", name)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        if !self.path.is_empty() {
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -50,9 +209,106 @@ pub struct ListingsByQuery {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(from = "RawListings")]
 pub struct Listings {
     pub totalCount: u32,
     pub edges: Vec<Edge>,
+    /// Edges left out because they could not be parsed.
+    #[serde(skip)]
+    pub warnings: Vec<ParseWarning>,
+}
+
+// Edges are parsed one by one so a single malformed listing does not fail
+// the whole page
+#[derive(Deserialize)]
+struct RawListings {
+    totalCount: u32,
+    #[serde(default)]
+    edges: Vec<Value>,
+}
+
+impl From<RawListings> for Listings {
+    fn from(raw: RawListings) -> Self {
+        let mut edges = Vec::new();
+        let mut warnings = Vec::new();
+        for (index, edge) in raw.edges.into_iter().enumerate() {
+            let listing_id = edge
+                .pointer("/node/listingID")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            match serde_path_to_error::deserialize::<_, Edge>(edge) {
+                Ok(edge) => edges.push(edge),
+                Err(e) => warnings.push(ParseWarning {
+                    index,
+                    listing_id,
+                    message: e.to_string(),
+                }),
+            }
+        }
+        Listings {
+            totalCount: raw.totalCount,
+            edges,
+            warnings,
+        }
+    }
+}
+
+/// A listing that was left out of the results because it could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseWarning {
+    /// Position of the edge within its page.
+    pub index: usize,
+    /// The ID of the listing, if it has one.
+    pub listing_id: Option<String>,
+    /// What failed, with the path of the offending field, e.g.
+    /// "node.listingID: invalid type: integer `7`, expected a string".
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.listing_id {
+            Some(id) => write!(f, "listing {} (edge {}): {}", id, self.index, self.message),
+            None => write!(f, "edge {}: {}", self.index, self.message),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CategoriesData {
+    pub categories: Vec<CategoryNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CategoryNode {
+    pub categoryID: String,
+    pub label: String,
+    #[serde(default)]
+    pub children: Vec<CategoryNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SellerListingsData {
+    /// `None` if there is no such seller.
+    pub sellerProfile: Option<SellerProfileNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListingData {
+    /// `None` if the listing does not exist or was removed.
+    pub listing: Option<ListingNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SellerProfileNode {
+    #[serde(default)]
+    pub sellerID: Option<String>,
+    pub alias: String,
+    #[serde(default)]
+    pub memberSince: Option<String>,
+    #[serde(default)]
+    pub postcodeInformation: Option<PostcodeInformation>,
+    pub listings: Listings,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -60,19 +316,132 @@ pub struct Edge {
     pub node: ListingNode,
 }
 
+/// A listing. Only `listingID` is required: missing or null text fields are
+/// left empty, and a thumbnail or location that does not parse is dropped.
+/// `diagnose::diagnose_search` reports such differences to the schema.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ListingNode {
     pub listingID: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub title: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub body: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub timestamp: String,
+    #[serde(default, deserialize_with = "or_none")]
     pub formattedPrice: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub sellerInfo: SellerInfo,
+    #[serde(default, deserialize_with = "or_none")]
     pub thumbnail: Option<Thumbnail>,
+    #[serde(default, deserialize_with = "or_none")]
+    pub postcodeInformation: Option<PostcodeInformation>,
+    /// Fields the crate does not model, e.g. selected by a custom search
+    /// document.
+    #[serde(flatten)]
+    pub unknown: serde_json::Map<String, Value>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl ListingNode {
+    /// Parses `formattedPrice` (e.g. "CHF 1'250.–") into a number.
+    pub fn price(&self) -> Option<f64> {
+        self.formattedPrice.as_deref().and_then(parse_price)
+    }
+
+    /// Parses `timestamp` into a date and time.
+    pub fn posted_at(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.timestamp).ok()
+    }
+
+    /// Postcode and place name, e.g. "8000 Zürich".
+    pub fn location(&self) -> Option<String> {
+        self.postcodeInformation
+            .as_ref()
+            .map(|info| format!("{} {}", info.postcode, info.locationName))
+    }
+
+    /// URL of the thumbnail, if the listing has a picture.
+    pub fn thumbnail_url(&self) -> Option<&str> {
+        self.thumbnail
+            .as_ref()
+            .and_then(|thumbnail| thumbnail.normalRendition.as_ref())
+            .map(|rendition| rendition.src.as_str())
+    }
+}
+
+/// Builds listings for tests, e.g.
+/// `ListingNode::test("1").with_price("CHF 50.–").with_seller("velofan")`.
+#[cfg(any(test, feature = "test-util"))]
+impl ListingNode {
+    /// A listing with nothing but an ID.
+    pub fn test(id: &str) -> Self {
+        ListingNode {
+            listingID: id.to_string(),
+            title: String::new(),
+            body: String::new(),
+            timestamp: String::new(),
+            formattedPrice: None,
+            sellerInfo: SellerInfo::default(),
+            thumbnail: None,
+            postcodeInformation: None,
+            unknown: serde_json::Map::new(),
+        }
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn with_body(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: &str) -> Self {
+        self.timestamp = timestamp.to_string();
+        self
+    }
+
+    /// Sets `formattedPrice`, e.g. "CHF 1'250.–".
+    pub fn with_price(mut self, formatted_price: &str) -> Self {
+        self.formattedPrice = Some(formatted_price.to_string());
+        self
+    }
+
+    pub fn with_seller(mut self, alias: &str) -> Self {
+        self.sellerInfo.alias = alias.to_string();
+        self
+    }
+
+    /// Sets the postcode, with an empty place name and no canton.
+    pub fn with_postcode(mut self, postcode: &str) -> Self {
+        self.postcodeInformation = Some(PostcodeInformation {
+            postcode: postcode.to_string(),
+            locationName: String::new(),
+            canton: None,
+        });
+        self
+    }
+}
+
+pub fn parse_price(price: &str) -> Option<f64> {
+    // Normalize to numeric characters and decimal point
+    let sanitized = price
+        .chars()
+        .filter_map(|c| match c {
+            '0'..='9' => Some(c),
+            '.' | ',' => Some('.'),
+            _ => None,
+        })
+        .collect::<String>();
+
+    sanitized.trim_end_matches('.').parse::<f64>().ok()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SellerInfo {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub alias: String,
 }
 
@@ -85,3 +454,114 @@ pub struct Thumbnail {
 pub struct Rendition {
     pub src: String,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PostcodeInformation {
+    #[serde(deserialize_with = "string_or_number")]
+    pub postcode: String,
+    pub locationName: String,
+    #[serde(default)]
+    pub canton: Option<Canton>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Canton {
+    /// E.g. "ZH".
+    pub shortName: String,
+    pub name: String,
+}
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+// For optional parts of a listing, which are better dropped than failing it
+fn or_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    Ok(serde_json::from_value(Value::deserialize(deserializer)?).ok())
+}
+
+// Postcodes are strings in the schema, but accept plain numbers as well
+fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        other => Err(serde::de::Error::custom(format!(
+            "expected a string or number, found {}",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_malformed_listings_are_skipped_or_degraded() {
+        let listings: Listings = serde_json::from_value(json!({
+            "totalCount": 3,
+            "edges": [
+                { "node": {
+                    "listingID": "1",
+                    "title": "Velo",
+                    "body": null,
+                    "timestamp": "2024-05-01T10:00:00+02:00",
+                    "formattedPrice": "CHF 80.–",
+                    "sellerInfo": { "alias": null },
+                    "thumbnail": { "normalRendition": { "width": 235 } },
+                    "postcodeInformation": { "postcode": 8001, "locationName": "Zürich" },
+                    "isFeatured": true
+                }},
+                { "node": { "listingID": 2, "title": "Kaputt" } },
+                { "node": { "listingID": "3", "sellerInfo": null } }
+            ]
+        }))
+        .unwrap();
+
+        let ids: Vec<&str> = listings
+            .edges
+            .iter()
+            .map(|edge| edge.node.listingID.as_str())
+            .collect();
+        assert_eq!(ids, ["1", "3"]);
+        let first = &listings.edges[0].node;
+        assert_eq!(first.body, "");
+        assert_eq!(first.sellerInfo.alias, "");
+        assert!(first.thumbnail.is_none());
+        assert_eq!(first.location().as_deref(), Some("8001 Zürich"));
+        assert_eq!(first.unknown["isFeatured"], json!(true));
+
+        assert_eq!(listings.warnings.len(), 1);
+        assert_eq!(listings.warnings[0].index, 1);
+        assert!(listings.warnings[0].message.starts_with("node.listingID"));
+    }
+
+    #[test]
+    fn test_read_search_document() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("search.graphql");
+
+        fs::write(&path, GRAPHQL_QUERY.replace("body\n", "")).unwrap();
+        assert!(!read_search_document(&path).unwrap().contains("body"));
+        fs::write(&path, CATEGORIES_QUERY).unwrap();
+        assert_eq!(
+            read_search_document(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
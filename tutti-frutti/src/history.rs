@@ -0,0 +1,216 @@
+use crate::analytics::{PriceStats, StatsOptions};
+use crate::graphql::ListingNode;
+use crate::util::query_key;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Price statistics of a query on one day.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DailySnapshot {
+    pub date: NaiveDate,
+    /// Number of listings found, priced or not.
+    pub listings: usize,
+    /// Number of listings with a parseable price. The price fields are 0
+    /// if there are none.
+    pub priced: usize,
+    pub min: f64,
+    pub q1: f64,
+    pub median: f64,
+    pub mean: f64,
+    pub q3: f64,
+    pub max: f64,
+}
+
+impl DailySnapshot {
+    pub fn from_listings(date: NaiveDate, listings: &[ListingNode]) -> Self {
+        let stats = PriceStats::from_listings(listings, &StatsOptions::default());
+        DailySnapshot {
+            date,
+            listings: listings.len(),
+            priced: stats.count,
+            min: stats.min,
+            q1: stats.q1,
+            median: stats.median,
+            mean: stats.mean,
+            q3: stats.q3,
+            max: stats.max,
+        }
+    }
+}
+
+/// One snapshot per query and day, oldest first.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PriceHistory {
+    queries: HashMap<String, Vec<DailySnapshot>>,
+}
+
+impl PriceHistory {
+    /// Loads the history from a JSON file, starting empty if it does not exist yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(PriceHistory::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Records the snapshot of a query for `date`. A later search on the same
+    /// day replaces the earlier snapshot.
+    pub fn record(&mut self, query: &str, date: NaiveDate, listings: &[ListingNode]) {
+        let snapshot = DailySnapshot::from_listings(date, listings);
+        let snapshots = self.queries.entry(query_key(query)).or_default();
+        match snapshots.binary_search_by_key(&date, |s| s.date) {
+            Ok(index) => snapshots[index] = snapshot,
+            Err(index) => snapshots.insert(index, snapshot),
+        }
+    }
+
+    pub fn snapshots(&self, query: &str) -> &[DailySnapshot] {
+        self.queries
+            .get(&query_key(query))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Queries with at least one snapshot.
+    pub fn queries(&self) -> impl Iterator<Item = &str> {
+        self.queries.keys().map(String::as_str)
+    }
+
+    pub fn trend(&self, query: &str) -> Trend {
+        Trend::from_snapshots(self.snapshots(query))
+    }
+}
+
+/// How the median price and the supply of a query develop over time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Trend {
+    /// Median price per day, skipping days without priced listings.
+    pub median: Vec<(NaiveDate, f64)>,
+    /// Number of listings per day.
+    pub supply: Vec<(NaiveDate, usize)>,
+    /// Least-squares slope of the median in CHF per day, `None` with fewer
+    /// than two days.
+    pub median_per_day: Option<f64>,
+    /// Least-squares slope of the supply in listings per day.
+    pub supply_per_day: Option<f64>,
+    /// Relative change of the median from the first to the last day, e.g.
+    /// -0.1 for a 10% drop.
+    pub median_change: Option<f64>,
+}
+
+impl Trend {
+    pub fn from_snapshots(snapshots: &[DailySnapshot]) -> Self {
+        let median: Vec<(NaiveDate, f64)> = snapshots
+            .iter()
+            .filter(|s| s.priced > 0)
+            .map(|s| (s.date, s.median))
+            .collect();
+        let supply: Vec<(NaiveDate, usize)> =
+            snapshots.iter().map(|s| (s.date, s.listings)).collect();
+
+        let median_change = match (median.first(), median.last()) {
+            (Some(&(first_day, first)), Some(&(last_day, last)))
+                if first_day != last_day && first > 0.0 =>
+            {
+                Some(last / first - 1.0)
+            }
+            _ => None,
+        };
+
+        Trend {
+            median_per_day: slope(&median),
+            supply_per_day: slope(
+                &supply
+                    .iter()
+                    .map(|&(date, count)| (date, count as f64))
+                    .collect::<Vec<_>>(),
+            ),
+            median_change,
+            median,
+            supply,
+        }
+    }
+}
+
+// Least-squares slope of values over days
+fn slope(points: &[(NaiveDate, f64)]) -> Option<f64> {
+    let (first_day, _) = *points.first()?;
+    let xs: Vec<f64> = points
+        .iter()
+        .map(|(date, _)| (*date - first_day).num_days() as f64)
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let variance: f64 = xs.iter().map(|x| (x - mean_x).powi(2)).sum();
+    if variance == 0.0 {
+        return None;
+    }
+    let covariance: f64 = xs
+        .iter()
+        .zip(points)
+        .map(|(x, (_, y))| (x - mean_x) * (y - mean_y))
+        .sum();
+    Some(covariance / variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listings(prices: &[u32]) -> Vec<ListingNode> {
+        prices
+            .iter()
+            .enumerate()
+            .map(|(i, price)| {
+                ListingNode::test(&i.to_string()).with_price(&format!("CHF {}.–", price))
+            })
+            .collect()
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn test_same_day_replaces_snapshot() {
+        let mut history = PriceHistory::default();
+        history.record("E-Bike", day(2), &listings(&[100, 200, 300]));
+        history.record("e-bike", day(1), &listings(&[100]));
+        history.record("e-bike ", day(2), &listings(&[400, 500]));
+
+        let snapshots = history.snapshots("e-bike");
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].date, day(1));
+        assert_eq!(snapshots[1].listings, 2);
+        assert_eq!(snapshots[1].median, 450.0);
+    }
+
+    #[test]
+    fn test_trend_slopes() {
+        let mut history = PriceHistory::default();
+        history.record("bike", day(1), &listings(&[100, 100, 100, 100]));
+        history.record("bike", day(2), &listings(&[90, 90, 90]));
+        history.record("bike", day(3), &listings(&[80, 80]));
+
+        let trend = history.trend("bike");
+        assert_eq!(trend.median_per_day, Some(-10.0));
+        assert_eq!(trend.supply_per_day, Some(-1.0));
+        assert!((trend.median_change.unwrap() + 0.2).abs() < 1e-9);
+
+        assert_eq!(history.trend("unknown"), Trend::default());
+    }
+}
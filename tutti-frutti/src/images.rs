@@ -0,0 +1,377 @@
+use crate::dedup::{find, union};
+use crate::errors::FetchListingsError;
+use crate::graphql::ListingNode;
+use crate::util::{hex, remove_entry, write_atomic};
+use crate::SearchConfig;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageResult};
+use reqwest::header::{HeaderMap, USER_AGENT};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Hashes at most this many bits apart are considered the same picture.
+pub const SIMILAR_DISTANCE: u32 = 10;
+
+/// A downloaded image with its decoded size.
+#[derive(Debug, Clone)]
+pub struct CachedImage {
+    pub url: String,
+    pub bytes: Vec<u8>,
+    /// SHA-256 of `bytes`, also the name of the file in the cache.
+    pub sha256: String,
+    pub width: u32,
+    pub height: u32,
+    pub hash: PerceptualHash,
+}
+
+impl CachedImage {
+    fn new(url: &str, bytes: Vec<u8>, sha256: String) -> ImageResult<Self> {
+        let image = image::load_from_memory(&bytes)?;
+        Ok(CachedImage {
+            url: url.to_string(),
+            sha256,
+            width: image.width(),
+            height: image.height(),
+            hash: PerceptualHash::of(&image),
+            bytes,
+        })
+    }
+
+    /// Decodes the image, e.g. to draw it.
+    pub fn decode(&self) -> ImageResult<DynamicImage> {
+        image::load_from_memory(&self.bytes)
+    }
+}
+
+/// 64-bit difference hash. Unlike a checksum it barely changes when a
+/// picture is re-encoded, resized or slightly cropped.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PerceptualHash(pub u64);
+
+impl PerceptualHash {
+    pub fn of(image: &DynamicImage) -> Self {
+        // Compare each pixel of a 9x8 grayscale thumbnail with its right neighbour
+        let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+        let mut hash = 0u64;
+        for y in 0..8 {
+            for x in 0..8 {
+                let left = small.get_pixel(x, y)[0];
+                let right = small.get_pixel(x + 1, y)[0];
+                hash = (hash << 1) | u64::from(left > right);
+            }
+        }
+        PerceptualHash(hash)
+    }
+
+    /// Number of differing bits, 0 for identical pictures.
+    pub fn distance(&self, other: &PerceptualHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+impl fmt::Display for PerceptualHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Downloads images into a content-addressed cache directory.
+///
+/// Images are stored once under their SHA-256, so the same picture reached
+/// through different URLs takes space only once. A small file per URL points
+/// to the content, which lets repeated lookups skip the network. The oldest
+/// images are removed once the cache is larger than `max_bytes`, 100 MB
+/// unless set with `with_max_bytes`.
+#[derive(Debug, Clone)]
+pub struct ImageCache {
+    dir: PathBuf,
+    client: Client,
+    max_bytes: u64,
+}
+
+impl ImageCache {
+    /// A cache downloading with a default client, without proxy or custom
+    /// certificates. See `with_config` to connect like searches do.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ImageCache::with_client(dir, Client::new())
+    }
+
+    /// A cache downloading through the proxy, with the user agent, timeouts
+    /// and certificates of `config`, like a `Session`.
+    pub fn with_config(
+        dir: impl Into<PathBuf>,
+        config: &SearchConfig,
+    ) -> Result<Self, FetchListingsError> {
+        let mut builder = Client::builder();
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(user_agent) = config.client.pick_user_agent()? {
+            let mut headers = HeaderMap::new();
+            headers.insert(USER_AGENT, user_agent);
+            builder = builder.default_headers(headers);
+        }
+        let client = config.client.apply(builder)?.build()?;
+        Ok(ImageCache::with_client(dir, client))
+    }
+
+    pub fn with_client(dir: impl Into<PathBuf>, client: Client) -> Self {
+        ImageCache {
+            dir: dir.into(),
+            client,
+            max_bytes: 100 * 1024 * 1024,
+        }
+    }
+
+    /// Limits the size of the downloaded images.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the image at `url`, downloading it unless it is cached.
+    pub async fn fetch(&self, url: &str) -> Result<CachedImage, FetchListingsError> {
+        if let Some(image) = self.cached(url)? {
+            return Ok(image);
+        }
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(FetchListingsError::from_status(response.status(), None));
+        }
+        let bytes = response.bytes().await?;
+        Ok(self.store(url, bytes.to_vec())?)
+    }
+
+    /// Returns the thumbnail of a listing, or `None` if it has no picture.
+    pub async fn fetch_thumbnail(
+        &self,
+        listing: &ListingNode,
+    ) -> Result<Option<CachedImage>, FetchListingsError> {
+        match listing.thumbnail_url() {
+            Some(url) => self.fetch(url).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Looks up an image without touching the network.
+    pub fn cached(&self, url: &str) -> io::Result<Option<CachedImage>> {
+        let sha256 = match fs::read_to_string(self.url_path(url)) {
+            Ok(sha256) => sha256.trim().to_string(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let bytes = match fs::read(self.object_path(&sha256)) {
+            Ok(bytes) => bytes,
+            // The content was removed, e.g. to free space, download it again
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        CachedImage::new(url, bytes, sha256)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Validates and writes downloaded bytes. Anything that does not decode
+    // is rejected rather than cached.
+    fn store(&self, url: &str, bytes: Vec<u8>) -> io::Result<CachedImage> {
+        let sha256 = hex(&Sha256::digest(&bytes));
+        let image = CachedImage::new(url, bytes, sha256)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let object = self.object_path(&image.sha256);
+        if !object.exists() {
+            write_atomic(&object, &image.bytes)?;
+        }
+        write_atomic(&self.url_path(url), image.sha256.as_bytes())?;
+        // Failing to make room is no reason to fail the download
+        let _ = self.evict();
+        Ok(image)
+    }
+
+    // Removes the oldest images beyond `max_bytes`, then the URLs pointing
+    // to them
+    fn evict(&self) -> io::Result<()> {
+        let mut objects = Vec::new();
+        for prefix in fs::read_dir(self.dir.join("objects"))? {
+            let prefix = prefix?;
+            if !prefix.file_type()?.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(prefix.path())? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                // Leaves alone files being written, named with an extension
+                if metadata.is_file() && entry.path().extension().is_none() {
+                    objects.push((metadata.modified()?, metadata.len(), entry.path()));
+                }
+            }
+        }
+        let mut total: u64 = objects.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+        objects.sort();
+        for (_, len, path) in objects {
+            if total <= self.max_bytes {
+                break;
+            }
+            remove_entry(&path)?;
+            total -= len;
+        }
+
+        for entry in fs::read_dir(self.dir.join("urls"))? {
+            let path = entry?.path();
+            if path.extension().is_some() {
+                continue;
+            }
+            let sha256 = match fs::read_to_string(&path) {
+                Ok(sha256) => sha256,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if !self.object_path(sha256.trim()).exists() {
+                remove_entry(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn object_path(&self, sha256: &str) -> PathBuf {
+        let (prefix, rest) = sha256.split_at(2.min(sha256.len()));
+        self.dir.join("objects").join(prefix).join(rest)
+    }
+
+    fn url_path(&self, url: &str) -> PathBuf {
+        self.dir.join("urls").join(hex(&Sha256::digest(url)))
+    }
+}
+
+/// Groups listings whose pictures are at most `max_distance` bits apart,
+/// e.g. the same photo used in several listings. Only groups of at least two
+/// listings are returned, in the order of their first listing.
+pub fn find_duplicate_images(
+    hashes: &[(String, PerceptualHash)],
+    max_distance: u32,
+) -> Vec<Vec<String>> {
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    for (a, (_, hash_a)) in hashes.iter().enumerate() {
+        for (b, (_, hash_b)) in hashes.iter().enumerate().skip(a + 1) {
+            if hash_a.distance(hash_b) <= max_distance {
+                union(&mut parents, a, b);
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<String>> = HashMap::new();
+    for (i, (listing_id, _)) in hashes.iter().enumerate() {
+        let root = find(&mut parents, i);
+        groups.entry(root).or_default().push(listing_id.clone());
+    }
+    let mut groups: Vec<(usize, Vec<String>)> = groups
+        .into_iter()
+        .filter(|(_, ids)| ids.len() > 1)
+        .collect();
+    groups.sort_by_key(|(root, _)| *root);
+    groups.into_iter().map(|(_, ids)| ids).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, ImageFormat, Luma};
+    use std::io::Cursor;
+
+    // Horizontal gradient, optionally mirrored
+    fn gradient(width: u32, height: u32, mirrored: bool) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, _| {
+            let x = if mirrored { width - 1 - x } else { x };
+            Luma([(x * 255 / (width - 1)) as u8])
+        }))
+    }
+
+    fn png(image: &DynamicImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_hash_survives_resizing() {
+        let original = PerceptualHash::of(&gradient(235, 167, false));
+        let resized = PerceptualHash::of(&gradient(120, 80, false));
+        let mirrored = PerceptualHash::of(&gradient(235, 167, true));
+        assert!(original.distance(&resized) <= SIMILAR_DISTANCE);
+        assert!(original.distance(&mirrored) > SIMILAR_DISTANCE);
+
+        let groups = find_duplicate_images(
+            &[
+                ("a".to_string(), original),
+                ("b".to_string(), mirrored),
+                ("c".to_string(), resized),
+            ],
+            SIMILAR_DISTANCE,
+        );
+        assert_eq!(groups, [["a", "c"]]);
+    }
+
+    #[test]
+    fn test_cache_is_content_addressed() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", uuid::Uuid::new_v4()));
+        let cache = ImageCache::new(&dir);
+        let bytes = png(&gradient(20, 10, false));
+
+        let first = cache
+            .store("https://example.com/a.png", bytes.clone())
+            .unwrap();
+        cache.store("https://example.com/b.png", bytes).unwrap();
+        assert_eq!((first.width, first.height), (20, 10));
+
+        let cached = cache.cached("https://example.com/b.png").unwrap().unwrap();
+        assert_eq!(cached.sha256, first.sha256);
+        assert_eq!(cached.hash, first.hash);
+        assert!(cache.cached("https://example.com/c.png").unwrap().is_none());
+        assert_eq!(fs::read_dir(dir.join("objects")).unwrap().count(), 1);
+        assert!(cache
+            .store("https://example.com/d.png", b"<html>".to_vec())
+            .is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_oldest_images_are_evicted() {
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", uuid::Uuid::new_v4()));
+        let old = png(&gradient(20, 10, false));
+        let new = png(&gradient(20, 10, true));
+        // Room for the newer image only
+        let cache = ImageCache::new(&dir).with_max_bytes(new.len() as u64);
+
+        cache.store("https://example.com/old.png", old).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.store("https://example.com/new.png", new).unwrap();
+        assert!(cache
+            .cached("https://example.com/old.png")
+            .unwrap()
+            .is_none());
+        assert!(cache
+            .cached("https://example.com/new.png")
+            .unwrap()
+            .is_some());
+        // The URL of the removed image is forgotten too
+        assert_eq!(fs::read_dir(dir.join("urls")).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
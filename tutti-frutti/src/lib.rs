@@ -1,52 +1,74 @@
+pub mod analytics;
+pub mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+pub mod cassette;
+pub mod categories;
 pub mod client;
+pub mod deals;
+pub mod dedup;
+pub mod diagnose;
+pub mod errors;
+pub mod geo;
 pub mod graphql;
+pub mod history;
+pub mod images;
+pub mod locale;
+pub mod network;
+pub mod seller;
+pub mod session;
+pub mod synonyms;
 pub mod util;
 
-use client::{init_session, perform_request};
-use graphql::{ListingNode, FIRST};
-use reqwest::Client;
-use std::error::Error;
-use std::sync::Arc;
+use cache::ResponseCache;
+use cassette::Cassette;
+use client::Session;
+use errors::FetchListingsError;
+use geo::DistanceFilter;
+use graphql::ListingNode;
+use locale::Locale;
+use network::ClientOptions;
+use std::path::PathBuf;
+use std::time::Duration;
 
-pub async fn fetch_listings(
-    search_query: &str,
-) -> Result<Vec<ListingNode>, Box<dyn Error + Send + Sync>> {
-    let cookie_store = Arc::new(reqwest::cookie::Jar::default());
-    let client = Client::builder()
-        .cookie_provider(cookie_store.clone())
-        .build()?;
-
-    let csrf_token = init_session(&client, &cookie_store).await?;
-
-    let (total_count, first_page_listings) =
-        perform_request(&client, &csrf_token, search_query, 0).await?;
-
-    let mut all_listings = first_page_listings;
-    let total_pages = ((total_count + FIRST - 1) / FIRST) as usize;
-
-    // Fetch remaining pages concurrently
-    let mut tasks = vec![];
-    for page in 1..total_pages {
-        let offset = page as u32 * FIRST;
-        let client = client.clone();
-        let csrf_token = csrf_token.clone();
-        let search_query = search_query.to_string();
-
-        tasks.push(tokio::spawn(async move {
-            perform_request(&client, &csrf_token, &search_query, offset).await
-        }));
-    }
+/// Network settings for fetching listings.
+#[derive(Debug, Clone, Default)]
+pub struct SearchConfig {
+    /// Maximum number of pages to fetch, `None` fetches all of them.
+    pub max_pages: Option<usize>,
+    /// Timeout for each request, `None` waits indefinitely.
+    pub timeout: Option<Duration>,
+    /// Only return listings within a distance of home.
+    pub distance: Option<DistanceFilter>,
+    /// Language of the request headers and referer.
+    pub locale: Locale,
+    /// Replaces `graphql::GRAPHQL_QUERY`, see `graphql::read_search_document`.
+    pub search_document: Option<String>,
+    /// Records requests to, or replays them from, a file.
+    pub cassette: Option<Cassette>,
+    /// Caches GraphQL responses on disk.
+    pub cache: Option<ResponseCache>,
+    /// Saves the session to reuse it in later runs instead of visiting
+    /// tutti.ch first, see `session::StoredSession`.
+    pub session_file: Option<PathBuf>,
+    /// Proxy, user agents, connection timeouts and root certificates.
+    pub client: ClientOptions,
+}
 
-    let results = futures::future::join_all(tasks).await;
-    for result in results {
-        let (_, listings) = result??;
-        all_listings.extend(listings);
-    }
+pub async fn fetch_listings(search_query: &str) -> Result<Vec<ListingNode>, FetchListingsError> {
+    fetch_listings_with_config(search_query, &SearchConfig::default()).await
+}
 
-    Ok(all_listings)
+pub async fn fetch_listings_with_config(
+    search_query: &str,
+    config: &SearchConfig,
+) -> Result<Vec<ListingNode>, FetchListingsError> {
+    Session::start(config).await?.search(search_query).await
 }
 
 #[cfg(test)]
+#[allow(unused_imports)]
 mod tests {
     use super::*;
     use crate::fetch_listings;
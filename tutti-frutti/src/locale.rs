@@ -0,0 +1,91 @@
+use crate::graphql::ListingNode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Language of the tutti.ch pages, listing URLs and request headers.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    De,
+    Fr,
+    It,
+    En,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 4] = [Locale::De, Locale::Fr, Locale::It, Locale::En];
+
+    /// Parses a language code like "fr", ignoring case.
+    pub fn from_code(code: &str) -> Option<Self> {
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.code().eq_ignore_ascii_case(code.trim()))
+    }
+
+    /// Language code as used in tutti.ch URLs.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::De => "de",
+            Locale::Fr => "fr",
+            Locale::It => "it",
+            Locale::En => "en",
+        }
+    }
+
+    /// Value of the `Accept-Language` header, falling back to English.
+    pub fn accept_language(&self) -> &'static str {
+        match self {
+            Locale::De => "de-CH,de;q=0.9,en-US;q=0.7,en;q=0.3",
+            Locale::Fr => "fr-CH,fr;q=0.9,en-US;q=0.7,en;q=0.3",
+            Locale::It => "it-CH,it;q=0.9,en-US;q=0.7,en;q=0.3",
+            Locale::En => "en-US,en;q=0.5",
+        }
+    }
+
+    /// Path of the search results page, e.g. "/fr/q/recherche".
+    pub fn search_path(&self) -> String {
+        let page = match self {
+            Locale::De => "suche",
+            Locale::Fr => "recherche",
+            Locale::It => "ricerca",
+            Locale::En => "search",
+        };
+        format!("/{}/q/{}", self.code(), page)
+    }
+
+    /// Web page of a listing.
+    pub fn listing_url(&self, listing_id: &str) -> String {
+        format!("https://www.tutti.ch/{}/vi/{}", self.code(), listing_id)
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl ListingNode {
+    /// Web page of the listing in the given language.
+    pub fn url(&self, locale: Locale) -> String {
+        locale.listing_url(&self.listingID)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_and_urls() {
+        assert_eq!(Locale::from_code(" FR"), Some(Locale::Fr));
+        assert_eq!(Locale::from_code("rm"), None);
+        assert_eq!(
+            Locale::It.listing_url("123"),
+            "https://www.tutti.ch/it/vi/123"
+        );
+        assert_eq!(Locale::Fr.search_path(), "/fr/q/recherche");
+        assert!(Locale::Fr.accept_language().starts_with("fr"));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::errors::FetchListingsError;
+use reqwest::header::HeaderValue;
+use reqwest::{Certificate, ClientBuilder, Proxy};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+compile_error!("enable the native-tls or the rustls feature of tutti-frutti");
+
+/// How the HTTP client connects, set as `SearchConfig::client`.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    /// Sends all requests through a proxy, e.g. "http://proxy:3128" or
+    /// "socks5h://localhost:1080". Without one, the proxy of the
+    /// environment (`HTTPS_PROXY` etc.) is used.
+    pub proxy: Option<String>,
+    /// User agents to pick from at random for each session, instead of
+    /// "Mozilla/5.0".
+    pub user_agents: Vec<String>,
+    /// Timeout for establishing a connection.
+    pub connect_timeout: Option<Duration>,
+    /// Timeout for each read of a response, unlike `SearchConfig::timeout`
+    /// which limits the whole request.
+    pub read_timeout: Option<Duration>,
+    /// PEM files with root certificates to trust in addition to the system
+    /// ones, e.g. that of a TLS-intercepting corporate proxy.
+    pub root_certificates: Vec<PathBuf>,
+}
+
+impl ClientOptions {
+    /// Applies the options to a client builder.
+    pub fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder, FetchListingsError> {
+        #[cfg(feature = "rustls")]
+        {
+            builder = builder.use_rustls_tls();
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        for path in &self.root_certificates {
+            let pem = fs::read(path).map_err(|e| {
+                FetchListingsError::InvalidRequest(format!(
+                    "root certificate {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            for certificate in Certificate::from_pem_bundle(&pem)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(builder)
+    }
+
+    /// One of `user_agents` at random, `None` if there are none.
+    pub fn pick_user_agent(&self) -> Result<Option<HeaderValue>, FetchListingsError> {
+        if self.user_agents.is_empty() {
+            return Ok(None);
+        }
+        let index = (Uuid::new_v4().as_u128() % self.user_agents.len() as u128) as usize;
+        let user_agent = &self.user_agents[index];
+        HeaderValue::from_str(user_agent)
+            .map(Some)
+            .map_err(|_| FetchListingsError::InvalidRequest(format!("user agent {:?}", user_agent)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_options_are_reported() {
+        let options = ClientOptions {
+            user_agents: vec!["frutti/1.0".to_string()],
+            ..ClientOptions::default()
+        };
+        assert_eq!(options.pick_user_agent().unwrap().unwrap(), "frutti/1.0");
+        assert!(ClientOptions::default()
+            .pick_user_agent()
+            .unwrap()
+            .is_none());
+
+        let missing = ClientOptions {
+            root_certificates: vec![PathBuf::from("/nonexistent/corporate.pem")],
+            ..ClientOptions::default()
+        };
+        assert!(matches!(
+            missing.apply(reqwest::Client::builder()),
+            Err(FetchListingsError::InvalidRequest(_))
+        ));
+        let proxy = ClientOptions {
+            proxy: Some("socks5h://localhost:1080".to_string()),
+            ..ClientOptions::default()
+        };
+        assert!(proxy
+            .apply(reqwest::Client::builder())
+            .unwrap()
+            .build()
+            .is_ok());
+    }
+}
@@ -0,0 +1,183 @@
+use crate::client::Session;
+use crate::errors::FetchListingsError;
+use crate::graphql::{
+    ListingNode, PostcodeInformation, SellerListingsData, SellerProfileNode, FIRST,
+    SELLER_LISTINGS_QUERY,
+};
+use crate::SearchConfig;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+
+/// Identifies a seller, usually by the alias shown with their listings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SellerRef {
+    Alias(String),
+    Id(String),
+}
+
+impl From<&str> for SellerRef {
+    fn from(alias: &str) -> Self {
+        SellerRef::Alias(alias.to_string())
+    }
+}
+
+impl fmt::Display for SellerRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SellerRef::Alias(alias) => write!(f, "seller '{}'", alias),
+            SellerRef::Id(id) => write!(f, "seller with ID {}", id),
+        }
+    }
+}
+
+impl ListingNode {
+    /// The seller of the listing, `None` if the API sent no alias.
+    pub fn seller_ref(&self) -> Option<SellerRef> {
+        let alias = self.sellerInfo.alias.trim();
+        (!alias.is_empty()).then(|| SellerRef::Alias(alias.to_string()))
+    }
+}
+
+/// What tutti.ch shows about a seller.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SellerProfile {
+    pub seller_id: Option<String>,
+    pub alias: String,
+    /// When the seller registered, as sent by the API.
+    pub member_since: Option<String>,
+    /// Number of listings online, including any beyond `max_pages`.
+    pub listing_count: u32,
+    /// Only set if the seller made it public.
+    pub location: Option<PostcodeInformation>,
+}
+
+impl SellerProfile {
+    fn new(node: &SellerProfileNode) -> Self {
+        SellerProfile {
+            seller_id: node.sellerID.clone(),
+            alias: node.alias.clone(),
+            member_since: node.memberSince.clone(),
+            listing_count: node.listings.totalCount,
+            location: node.postcodeInformation.clone(),
+        }
+    }
+
+    /// Parses `member_since` into a date and time.
+    pub fn member_since_date(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(self.member_since.as_deref()?).ok()
+    }
+}
+
+/// Fetches the profile and listings of a seller, newest first.
+pub async fn fetch_seller_listings(
+    seller: impl Into<SellerRef>,
+    config: &SearchConfig,
+) -> Result<(SellerProfile, Vec<ListingNode>), FetchListingsError> {
+    Session::start(config)
+        .await?
+        .seller_listings(&seller.into())
+        .await
+}
+
+impl Session {
+    /// Like `fetch_seller_listings`, over this session.
+    pub async fn seller_listings(
+        &self,
+        seller: &SellerRef,
+    ) -> Result<(SellerProfile, Vec<ListingNode>), FetchListingsError> {
+        // An empty alias would match some other profile or none at all
+        if matches!(seller, SellerRef::Alias(alias) if alias.trim().is_empty()) {
+            return Err(FetchListingsError::InvalidRequest(
+                "the seller alias is empty".to_string(),
+            ));
+        }
+        let first = self.seller_page(seller, 0).await?;
+        let profile = SellerProfile::new(&first);
+
+        let mut total_pages = first.listings.totalCount.div_ceil(FIRST) as usize;
+        if let Some(max_pages) = self.config().max_pages {
+            total_pages = total_pages.min(max_pages);
+        }
+        let pages = futures::future::join_all(
+            (1..total_pages).map(|page| self.seller_page(seller, page as u32 * FIRST)),
+        )
+        .await;
+
+        let mut listings: Vec<ListingNode> = first
+            .listings
+            .edges
+            .into_iter()
+            .map(|edge| edge.node)
+            .collect();
+        for page in pages {
+            listings.extend(page?.listings.edges.into_iter().map(|edge| edge.node));
+        }
+        if let Some(filter) = &self.config().distance {
+            listings = filter.apply(listings);
+        }
+        Ok((profile, listings))
+    }
+
+    async fn seller_page(
+        &self,
+        seller: &SellerRef,
+        offset: u32,
+    ) -> Result<SellerProfileNode, FetchListingsError> {
+        let (alias, seller_id) = match seller {
+            SellerRef::Alias(alias) => (Some(alias), None),
+            SellerRef::Id(id) => (None, Some(id)),
+        };
+        let variables = json!({
+            "alias": alias,
+            "sellerID": seller_id,
+            "first": FIRST,
+            "offset": offset
+        });
+        let data: SellerListingsData = self.execute(SELLER_LISTINGS_QUERY, variables).await?;
+        let mut node = data
+            .sellerProfile
+            .ok_or_else(|| FetchListingsError::NotFound(seller.to_string()))?;
+        self.record_warnings(std::mem::take(&mut node.listings.warnings));
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_from_response() {
+        let data: SellerListingsData = serde_json::from_value(json!({
+            "sellerProfile": {
+                "sellerID": "42",
+                "alias": "velofan",
+                "memberSince": "2016-03-01T09:00:00+01:00",
+                "postcodeInformation": { "postcode": 8001, "locationName": "Zürich" },
+                "listings": { "totalCount": 31, "edges": [] }
+            }
+        }))
+        .unwrap();
+        let profile = SellerProfile::new(data.sellerProfile.as_ref().unwrap());
+        assert_eq!(profile.seller_id.as_deref(), Some("42"));
+        assert_eq!(profile.listing_count, 31);
+        assert_eq!(profile.location.as_ref().unwrap().postcode, "8001");
+        assert_eq!(
+            profile.member_since_date().unwrap().date_naive(),
+            chrono::NaiveDate::from_ymd_opt(2016, 3, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_listing_without_alias_has_no_seller() {
+        let listing = ListingNode::test("1").with_seller("velofan");
+        assert_eq!(
+            listing.seller_ref(),
+            Some(SellerRef::Alias("velofan".to_string()))
+        );
+        assert_eq!(ListingNode::test("1").seller_ref(), None);
+        assert_eq!(ListingNode::test("1").with_seller(" ").seller_ref(), None);
+    }
+}
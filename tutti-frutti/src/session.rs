@@ -0,0 +1,171 @@
+use chrono::{DateTime, Duration, Utc};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// How long a session without a cookie expiry is reused.
+pub const DEFAULT_LIFETIME: Duration = Duration::hours(24);
+
+/// The cookies and CSRF token of a visit to tutti.ch, saved to skip the
+/// handshake next time, see `SearchConfig::session_file`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StoredSession {
+    pub csrf_token: String,
+    /// "name=value" pairs sent to www.tutti.ch.
+    pub cookies: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// When the CSRF cookie expires, or `DEFAULT_LIFETIME` after creation.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl StoredSession {
+    /// Captures the cookies of `jar` after a handshake whose response had
+    /// `headers`, which tell when the CSRF cookie expires.
+    pub fn capture(jar: &Jar, csrf_token: String, headers: &HeaderMap) -> Self {
+        let cookies = jar
+            .cookies(&tutti_url())
+            .and_then(|cookies| cookies.to_str().ok().map(str::to_string))
+            .map(|cookies| {
+                cookies
+                    .split(';')
+                    .map(|cookie| cookie.trim().to_string())
+                    .filter(|cookie| !cookie.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let created_at = Utc::now();
+        let expires_at = headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter(|value| value.trim_start().starts_with("tutti_csrftoken="))
+            .find_map(|value| cookie_expiry(value, created_at))
+            .unwrap_or(created_at + DEFAULT_LIFETIME);
+        StoredSession {
+            csrf_token,
+            cookies,
+            created_at,
+            expires_at,
+        }
+    }
+
+    /// Loads a saved session, `None` if there is none.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Saves the session, readable only by the current user where supported.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(path)?
+            .write_all(serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+
+    /// Puts the cookies back into `jar`.
+    pub fn restore(&self, jar: &Jar) {
+        let url = tutti_url();
+        for cookie in &self.cookies {
+            jar.add_cookie_str(cookie, &url);
+        }
+    }
+}
+
+fn tutti_url() -> Url {
+    "https://www.tutti.ch/".parse().unwrap()
+}
+
+// From the Max-Age or Expires attribute of a Set-Cookie header
+fn cookie_expiry(set_cookie: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let attributes = || set_cookie.split(';').skip(1).map(str::trim);
+    let attribute = |name: &str| {
+        attributes().find_map(|attribute| {
+            let (key, value) = attribute.split_once('=')?;
+            key.eq_ignore_ascii_case(name).then_some(value)
+        })
+    };
+    if let Some(seconds) = attribute("max-age").and_then(|value| value.parse().ok()) {
+        return Some(now + Duration::seconds(seconds));
+    }
+    DateTime::parse_from_rfc2822(attribute("expires")?)
+        .ok()
+        .map(|expires| expires.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_session_round_trip() {
+        let jar = Jar::default();
+        let url = tutti_url();
+        jar.add_cookie_str("tutti_csrftoken=abc; Path=/", &url);
+        jar.add_cookie_str("tutti_session=xyz; Path=/", &url);
+        let mut headers = HeaderMap::new();
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("tutti_csrftoken=abc; Path=/; Max-Age=3600"),
+        );
+
+        let session = StoredSession::capture(&jar, "abc".to_string(), &headers);
+        let mut cookies = session.cookies.clone();
+        cookies.sort();
+        assert_eq!(cookies, ["tutti_csrftoken=abc", "tutti_session=xyz"]);
+        assert_eq!(
+            (session.expires_at - session.created_at).num_seconds(),
+            3600
+        );
+        assert!(!session.is_expired());
+
+        let dir = std::env::temp_dir().join(format!("tutti-frutti-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("session.json");
+        session.save(&path).unwrap();
+        let loaded = StoredSession::load(&path).unwrap().unwrap();
+        assert_eq!(loaded, session);
+
+        let restored = Jar::default();
+        loaded.restore(&restored);
+        let sent = restored.cookies(&url).unwrap();
+        assert!(sent.to_str().unwrap().contains("tutti_session=xyz"));
+        assert!(StoredSession::load(&dir.join("missing.json"))
+            .unwrap()
+            .is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cookie_expiry() {
+        let now = Utc::now();
+        assert_eq!(
+            cookie_expiry("a=b; Expires=Wed, 21 Oct 2026 07:28:00 GMT; Path=/", now)
+                .unwrap()
+                .to_rfc3339(),
+            "2026-10-21T07:28:00+00:00"
+        );
+        assert!(cookie_expiry("a=b; Path=/", now).is_none());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::batch::{MergedListings, SearchRequest};
+use crate::client::Session;
+use crate::errors::FetchListingsError;
+use crate::graphql::ListingNode;
+use crate::util::query_key;
+use crate::SearchConfig;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Common words in German, French and Italian
+const BUILTIN: &str = include_str!("../data/synonyms.txt");
+
+/// At most this many variants are searched besides the original query.
+pub const MAX_VARIANTS: usize = 8;
+
+/// Groups of terms that mean the same, e.g. "velo, vélo, bicicletta".
+///
+/// The text format has one comma separated group per line, `#` starts a
+/// comment. Terms are matched ignoring case and may span several words.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Synonyms {
+    groups: Vec<Vec<String>>,
+}
+
+impl Synonyms {
+    /// The dictionary bundled with the crate.
+    pub fn builtin() -> Self {
+        Synonyms::parse(BUILTIN)
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let groups = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .filter_map(|line| {
+                let mut terms: Vec<String> = Vec::new();
+                for term in line.split(',').map(query_key) {
+                    if !term.is_empty() && !terms.contains(&term) {
+                        terms.push(term);
+                    }
+                }
+                (terms.len() > 1).then_some(terms)
+            })
+            .collect();
+        Synonyms { groups }
+    }
+
+    /// Loads a dictionary file, empty if it does not exist.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Synonyms::parse(&contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Synonyms::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Adds the groups of another dictionary, e.g. the user's own.
+    pub fn extend(&mut self, other: Synonyms) {
+        self.groups.extend(other.groups);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Number of groups.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Queries meaning the same as `query`, without the query itself. Each
+    /// variant replaces one term with one of its synonyms; where terms
+    /// overlap, the longest one is replaced.
+    pub fn variants(&self, query: &str) -> Vec<String> {
+        let words: Vec<&str> = query.split_whitespace().collect();
+        let lowercase: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+
+        // (start, length, group) of every term found in the query
+        let mut matches = Vec::new();
+        for (group_index, group) in self.groups.iter().enumerate() {
+            for term in group {
+                let term: Vec<&str> = term.split(' ').collect();
+                for start in 0..lowercase.len() {
+                    let end = start + term.len();
+                    if end <= lowercase.len() && lowercase[start..end] == term[..] {
+                        matches.push((start, term.len(), group_index));
+                    }
+                }
+            }
+        }
+        matches.sort_by_key(|&(start, len, _)| (std::cmp::Reverse(len), start));
+        let mut covered = vec![false; words.len()];
+        let mut kept = Vec::new();
+        for (start, len, group) in matches {
+            if covered[start..start + len].iter().all(|c| !c) {
+                covered[start..start + len]
+                    .iter_mut()
+                    .for_each(|c| *c = true);
+                kept.push((start, len, group));
+            }
+        }
+        kept.sort_by_key(|&(start, _, _)| start);
+
+        let mut seen = HashSet::from([query_key(query)]);
+        let mut variants = Vec::new();
+        for (start, len, group) in kept {
+            for replacement in &self.groups[group] {
+                let variant = [
+                    &words[..start],
+                    &[replacement.as_str()][..],
+                    &words[start + len..],
+                ]
+                .concat()
+                .join(" ");
+                if seen.insert(query_key(&variant)) {
+                    variants.push(variant);
+                }
+            }
+        }
+        variants.truncate(MAX_VARIANTS);
+        variants
+    }
+}
+
+/// Results of a query and its synonym variants.
+#[derive(Debug)]
+pub struct ExpandedListings {
+    /// The listings of the original query and of the variants that succeeded.
+    pub merged: MergedListings,
+    /// Variants whose search failed, with their errors.
+    pub failed: Vec<(String, FetchListingsError)>,
+}
+
+/// Searches a query and its synonym variants and merges the results, the
+/// original query first. With an empty dictionary this finds the same
+/// listings as `fetch_listings_with_config`. Only a failure of the original
+/// query is an error; failed variants are left out and reported in
+/// `ExpandedListings::failed`.
+pub async fn fetch_listings_expanded(
+    request: impl Into<SearchRequest>,
+    config: &SearchConfig,
+    synonyms: &Synonyms,
+) -> Result<ExpandedListings, FetchListingsError> {
+    let session = Session::start(config).await?;
+    search_expanded(&session, &request.into(), synonyms).await
+}
+
+/// Like `fetch_listings_expanded`, over an existing session.
+pub async fn search_expanded(
+    session: &Session,
+    request: &SearchRequest,
+    synonyms: &Synonyms,
+) -> Result<ExpandedListings, FetchListingsError> {
+    let mut requests = vec![request.clone()];
+    requests.extend(
+        synonyms
+            .variants(&request.query)
+            .into_iter()
+            .map(|query| SearchRequest {
+                query,
+                ..request.clone()
+            }),
+    );
+
+    let results =
+        futures::future::join_all(requests.iter().map(|request| session.fetch(request))).await;
+    collect_variants(requests, results)
+}
+
+// The original query comes first; its error is returned, those of the
+// variants are collected
+fn collect_variants(
+    requests: Vec<SearchRequest>,
+    results: Vec<Result<Vec<ListingNode>, FetchListingsError>>,
+) -> Result<ExpandedListings, FetchListingsError> {
+    let mut found = Vec::new();
+    let mut failed = Vec::new();
+    for (i, (request, result)) in requests.into_iter().zip(results).enumerate() {
+        match result {
+            Ok(listings) => found.push((request.query, listings)),
+            Err(e) if i == 0 => return Err(e),
+            Err(e) => failed.push((request.query, e)),
+        }
+    }
+    Ok(ExpandedListings {
+        merged: MergedListings::merge(found),
+        failed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variants_replace_longest_term() {
+        let synonyms = Synonyms::parse(
+            "# comment\n\
+             velo, Vélo, bicicletta\n\
+             e-bike, vélo électrique # trailing comment\n\
+             rot, rouge\n\
+             lonely\n",
+        );
+        assert_eq!(synonyms.len(), 3);
+        assert_eq!(
+            synonyms.variants("Velo rot"),
+            ["vélo rot", "bicicletta rot", "Velo rouge"]
+        );
+        // "vélo" alone is not replaced inside "vélo électrique"
+        assert_eq!(synonyms.variants("Vélo  Électrique"), ["e-bike"]);
+        assert!(synonyms.variants("sofa").is_empty());
+        assert!(!Synonyms::builtin().variants("velo").is_empty());
+    }
+
+    #[test]
+    fn test_failed_variants_are_reported() {
+        let listing = ListingNode::test("1").with_title("Velo");
+        let requests = || vec![SearchRequest::new("velo"), SearchRequest::new("vélo")];
+        let rate_limited = || FetchListingsError::RateLimited { retry_after: None };
+
+        let expanded = collect_variants(
+            requests(),
+            vec![Ok(vec![listing.clone()]), Err(rate_limited())],
+        )
+        .unwrap();
+        assert_eq!(expanded.merged.listings.len(), 1);
+        assert_eq!(expanded.merged.matched_by("1"), ["velo"]);
+        assert_eq!(expanded.failed.len(), 1);
+        assert_eq!(expanded.failed[0].0, "vélo");
+
+        assert!(matches!(
+            collect_variants(requests(), vec![Err(rate_limited()), Ok(vec![listing])]),
+            Err(FetchListingsError::RateLimited { .. })
+        ));
+    }
+}
@@ -1,15 +1,19 @@
+use crate::locale::Locale;
 use reqwest::header::{
     HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, CONNECTION, ORIGIN,
     USER_AGENT,
 };
+use std::fs;
+use std::io;
+use std::path::Path;
 
-pub fn init_headers() -> HeaderMap {
+pub fn init_headers(locale: Locale) -> HeaderMap {
     let mut headers = HeaderMap::new();
     headers.insert(USER_AGENT, HeaderValue::from_static("Mozilla/5.0"));
     headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
     headers.insert(
         ACCEPT_LANGUAGE,
-        HeaderValue::from_static("de,en-US;q=0.7,en;q=0.3"),
+        HeaderValue::from_static(locale.accept_language()),
     );
     headers.insert(
         ACCEPT_ENCODING,
@@ -19,3 +23,35 @@ pub fn init_headers() -> HeaderMap {
     headers.insert(CONNECTION, HeaderValue::from_static("keep-alive"));
     headers
 }
+
+/// Normalizes a search query for use as a storage key, so that queries
+/// differing only in case or spacing share the same history.
+pub fn query_key(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// Write to a temporary file first so readers never see half a file
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path)
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Another process may have removed it already
+pub(crate) fn remove_entry(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}